@@ -0,0 +1,34 @@
+//! Decodes arbitrary input, and checked encodings with a bit flipped, which
+//! must never decode.
+
+#![no_main]
+
+use hyperloglog::checked::Checksum;
+use hyperloglog::{HyperLogLog, Registers};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|input: (&[u8], u32, bool)| {
+    let (data, bit, xxhash) = input;
+
+    let mut registers = [0u8; 1024];
+    let _ = registers.decompress(data);
+    let _ = HyperLogLog::<[u8; 1024]>::from_base64(&String::from_utf8_lossy(data));
+
+    let mut hll = HyperLogLog::<[u8; 1024]>::default();
+    for item in data.chunks(3) {
+        hll.insert(&item);
+    }
+    let checksum = if xxhash {
+        Checksum::XxHash64
+    } else {
+        Checksum::Crc32c
+    };
+    let mut bytes = Vec::new();
+    hll.compress_checked_into(checksum, &mut bytes);
+    assert_eq!(registers.decompress(&bytes), Ok(()));
+
+    // Past the tags, which are checked but not by the checksum.
+    let bit = 24 + bit as usize % (bytes.len() * 8 - 24);
+    bytes[bit / 8] ^= 1 << (bit % 8);
+    assert!(registers.decompress(&bytes).is_err());
+});
@@ -0,0 +1,91 @@
+/* Generated by cbindgen from src/ffi.rs with `make header`. */
+
+#ifndef HYPERLOGLOG_H
+#define HYPERLOGLOG_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// A sketch of a precision chosen when it was created.
+typedef struct HllSketch HllSketch;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// An empty sketch with `2^precision` registers, or null if `precision`
+// isn't in `4..=18`. Free it with `hll_free`.
+struct HllSketch *hll_new(uint8_t precision);
+
+// Frees a sketch. Does nothing if `hll` is null.
+//
+// # Safety
+//
+// `hll` must be null, or a sketch that hasn't been freed.
+void hll_free(struct HllSketch *hll);
+
+// The precision `hll` was created with.
+//
+// # Safety
+//
+// `hll` must be a sketch that hasn't been freed.
+uint8_t hll_precision(const struct HllSketch *hll);
+
+// Counts the `len` bytes at `bytes` if they are distinct.
+//
+// # Safety
+//
+// `hll` must be a sketch that hasn't been freed, and `bytes` must point to
+// `len` readable bytes, or may be null if `len` is 0.
+void hll_insert(struct HllSketch *hll, const uint8_t *bytes, size_t len);
+
+// Counts an item by its 64-bit hash, from a hash other than SipHash, if it
+// is distinct. The low `precision` bits pick the register, and the rest
+// are counted.
+//
+// # Safety
+//
+// `hll` must be a sketch that hasn't been freed.
+void hll_insert_hash(struct HllSketch *hll, uint64_t hash);
+
+// Merges `other` into `hll`, returning false, and doing nothing, if their
+// precisions differ.
+//
+// # Safety
+//
+// `hll` and `other` must be sketches that haven't been freed.
+bool hll_merge(struct HllSketch *hll, const struct HllSketch *other);
+
+// Estimates the number of distinct items inserted.
+//
+// # Safety
+//
+// `hll` must be a sketch that hasn't been freed.
+uint64_t hll_cardinality(const struct HllSketch *hll);
+
+// Returns the length of the serialized sketch, and writes it to `out` if
+// `capacity` is at least that long, so calling this with a `capacity` of 0
+// measures it.
+//
+// # Safety
+//
+// `hll` must be a sketch that hasn't been freed, and `out` must point to
+// `capacity` writable bytes, or may be null if `capacity` is 0.
+size_t hll_serialize(const struct HllSketch *hll, uint8_t *out, size_t capacity);
+
+// Decodes the output of `hll_serialize` of a sketch of `precision`, or
+// returns null if it's invalid. Free it with `hll_free`.
+//
+// # Safety
+//
+// `bytes` must point to `len` readable bytes, or may be null if `len` is 0.
+struct HllSketch *hll_deserialize(uint8_t precision, const uint8_t *bytes, size_t len);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* HYPERLOGLOG_H */
@@ -0,0 +1,218 @@
+//! Conversion to and from the serialized Airlift HyperLogLog, as produced
+//! by Trino's `approx_set` and read by `merge` and `cardinality`.
+//!
+//! Both the sparse and dense v2 layouts are supported. Their number of
+//! buckets must be at least [`Registers::REGISTERS`], and they are folded
+//! down to it when reading. Items must be inserted with [`MurmurHash3`] to
+//! stay compatible with sketches built by Trino, which uses 2^11 buckets for
+//! the default `approx_set` error.
+
+pub use crate::murmur3::murmurhash3_x64_128;
+use crate::{fold, HyperLogLog, ItemHasher, Registers};
+use alloc::{vec, vec::Vec};
+use core::fmt;
+
+/// An error reading a serialized Airlift HyperLogLog.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AirliftError {
+    /// Missing header, or an unknown format.
+    InvalidHeader,
+    /// Fewer buckets than [`Registers::REGISTERS`].
+    PrecisionTooLow(u8),
+    /// Wrong length for the format and number of buckets.
+    InvalidLength,
+    /// Entries or bucket values Airlift never produces.
+    InvalidRegisters,
+}
+
+impl fmt::Display for AirliftError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidHeader => f.write_str("airlift hyperloglog invalid header"),
+            Self::PrecisionTooLow(precision) => {
+                write!(f, "airlift hyperloglog precision {precision} too low")
+            }
+            Self::InvalidLength => f.write_str("airlift hyperloglog invalid length"),
+            Self::InvalidRegisters => f.write_str("airlift hyperloglog invalid registers"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for AirliftError {}
+
+const SPARSE_V2: u8 = 2;
+const DENSE_V2: u8 = 3;
+/// Leading bits of the hash kept by sparse entries.
+const EXTENDED_PREFIX_BITS: u8 = 26;
+/// Bits of a sparse entry holding the leading zeros past the prefix.
+const VALUE_BITS: u8 = 6;
+/// Largest delta from the baseline held by a dense bucket, past which the
+/// rest is stored as an overflow.
+const MAX_DELTA: u8 = 15;
+
+impl<R: Registers> HyperLogLog<R> {
+    /// Reads a serialized Airlift HyperLogLog in the sparse or dense v2
+    /// layout.
+    pub fn from_airlift(bytes: &[u8]) -> Result<Self, AirliftError> {
+        let &[format, precision, ref data @ ..] = bytes else {
+            return Err(AirliftError::InvalidHeader);
+        };
+        if !(1..=EXTENDED_PREFIX_BITS).contains(&precision) {
+            return Err(AirliftError::InvalidHeader);
+        }
+        if precision < R::PRECISION {
+            return Err(AirliftError::PrecisionTooLow(precision));
+        }
+
+        let mut ret = Self::default();
+        let registers = ret.0.registers_mut();
+        let mut update = |index: usize, value: u8, from: u8| {
+            let (index, value) = fold(index, value, from, R::PRECISION);
+            registers[index] = registers[index].max(value);
+        };
+        match format {
+            SPARSE_V2 => {
+                let (count, entries) = split_u16(data).ok_or(AirliftError::InvalidLength)?;
+                if entries.len() != count as usize * 4 {
+                    return Err(AirliftError::InvalidLength);
+                }
+                for entry in entries.chunks_exact(4) {
+                    let entry = u32::from_le_bytes(entry.try_into().unwrap());
+                    let zeros = (entry & ((1 << VALUE_BITS) - 1)) as u8;
+                    if zeros > 64 - EXTENDED_PREFIX_BITS {
+                        return Err(AirliftError::InvalidRegisters);
+                    }
+                    let index = (entry >> VALUE_BITS) as usize;
+                    update(index, zeros + 1, EXTENDED_PREFIX_BITS);
+                }
+            }
+            DENSE_V2 => {
+                let (&baseline, rest) = data.split_first().ok_or(AirliftError::InvalidLength)?;
+                let buckets = 1usize << precision;
+                let deltas = rest.get(..buckets / 2).ok_or(AirliftError::InvalidLength)?;
+                let (count, overflows) =
+                    split_u16(&rest[deltas.len()..]).ok_or(AirliftError::InvalidLength)?;
+                let count = count as usize;
+                if overflows.len() != count * 3 {
+                    return Err(AirliftError::InvalidLength);
+                }
+                let (overflow_buckets, overflow_values) = overflows.split_at(count * 2);
+
+                let mut values = (0..buckets)
+                    .map(|bucket| get_delta(deltas, bucket))
+                    .collect::<Vec<_>>();
+                for (bucket, &overflow) in overflow_buckets.chunks_exact(2).zip(overflow_values) {
+                    let bucket = u16::from_le_bytes(bucket.try_into().unwrap()) as usize;
+                    match values.get_mut(bucket) {
+                        Some(value) if *value == MAX_DELTA => {
+                            *value = value.saturating_add(overflow)
+                        }
+                        _ => return Err(AirliftError::InvalidRegisters),
+                    }
+                }
+                for (index, value) in values.into_iter().enumerate() {
+                    let value = baseline.saturating_add(value);
+                    if value > 65 - precision {
+                        return Err(AirliftError::InvalidRegisters);
+                    }
+                    if value != 0 {
+                        update(index, value, precision);
+                    }
+                }
+            }
+            _ => return Err(AirliftError::InvalidHeader),
+        }
+        Ok(ret)
+    }
+
+    /// Writes a serialized Airlift HyperLogLog with [`Registers::REGISTERS`]
+    /// buckets.
+    ///
+    /// Sparse entries keep a longer prefix of the hash than the registers
+    /// do, so each set register is written as the entry of one hash that
+    /// could have set it. Trino counts sparse sketches by their number of
+    /// entries, which undercounts collisions, so the sparse layout is only
+    /// written while at most 1/64th of the registers are set, where that
+    /// stays within about 1% of [`HyperLogLog::cardinality`]. Otherwise, the
+    /// dense v2 layout is written.
+    ///
+    /// # Panics
+    ///
+    /// If there are more than 2^16 registers, which dense v2 can't index.
+    pub fn to_airlift(&self) -> Vec<u8> {
+        assert!(R::PRECISION <= 16, "{}", R::PRECISION);
+        let registers = self.0.registers();
+        let non_zero = registers.len() - bytecount::count(registers, 0);
+
+        if non_zero <= registers.len() / 64 {
+            let mut ret = Vec::with_capacity(4 + non_zero * 4);
+            ret.extend_from_slice(&[SPARSE_V2, R::PRECISION]);
+            ret.extend_from_slice(&(non_zero as u16).to_le_bytes());
+            let extra_bits = EXTENDED_PREFIX_BITS - R::PRECISION;
+            for (index, &value) in registers.iter().enumerate().filter(|(_, &v)| v != 0) {
+                let prefix = (index as u32) << extra_bits;
+                // Place the first one bit after the index where the value
+                // says, within the rest of the prefix if it fits.
+                let entry = if value <= extra_bits {
+                    (prefix | 1 << (extra_bits - value)) << VALUE_BITS
+                } else {
+                    prefix << VALUE_BITS | (value - 1 - extra_bits) as u32
+                };
+                ret.extend_from_slice(&entry.to_le_bytes());
+            }
+            return ret;
+        }
+
+        let baseline = registers.iter().copied().min().unwrap_or(0);
+        let mut ret = vec![DENSE_V2, R::PRECISION, baseline];
+        let mut overflows = Vec::new();
+        let mut deltas = vec![0; registers.len() / 2];
+        for (bucket, &value) in registers.iter().enumerate() {
+            let delta = value - baseline;
+            if delta > MAX_DELTA {
+                overflows.push((bucket as u16, delta - MAX_DELTA));
+            }
+            deltas[bucket / 2] |= delta.min(MAX_DELTA) << shift_for_bucket(bucket);
+        }
+        ret.extend_from_slice(&deltas);
+        ret.extend_from_slice(&(overflows.len() as u16).to_le_bytes());
+        for &(bucket, _) in &overflows {
+            ret.extend_from_slice(&bucket.to_le_bytes());
+        }
+        ret.extend(overflows.iter().map(|&(_, overflow)| overflow));
+        ret
+    }
+}
+
+fn split_u16(data: &[u8]) -> Option<(u16, &[u8])> {
+    let (value, rest) = data.split_first_chunk::<2>()?;
+    Some((u16::from_le_bytes(*value), rest))
+}
+
+/// Even buckets are stored in the high nibble.
+fn shift_for_bucket(bucket: usize) -> u8 {
+    (!bucket as u8 & 1) << 2
+}
+
+fn get_delta(deltas: &[u8], bucket: usize) -> u8 {
+    deltas[bucket / 2] >> shift_for_bucket(bucket) & MAX_DELTA
+}
+
+/// Hashes items like Trino's `approx_set`: the first half of
+/// [`murmurhash3_x64_128`] with a seed of 0, bucket index from the high bits,
+/// and value from the leading zeros of the rest.
+///
+/// `bigint` values hash their little-endian bytes, and `varchar` and
+/// `varbinary` values hash their bytes as is.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct MurmurHash3;
+
+impl ItemHasher for MurmurHash3 {
+    fn register(&self, item: &[u8], precision: u8) -> (usize, u8) {
+        let hash = murmurhash3_x64_128(item, 0).0;
+        let index = (hash >> (64 - precision)) as usize;
+        let rest = hash << precision | 1 << (precision - 1);
+        (index, rest.leading_zeros() as u8 + 1)
+    }
+}
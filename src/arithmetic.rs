@@ -0,0 +1,282 @@
+//! The adaptive arithmetic coder of the dense encodings. It produces the
+//! same bits as version 0.2 of the `arcode` crate, which they were first
+//! written with, down to its floating point rounding, and must keep doing
+//! so.
+
+use crate::io::{self, Read, Write};
+use crate::DecompressError;
+
+/// The most symbols of any dense model.
+const MAX_SYMBOLS: usize = 64;
+
+/// Counts of the symbols seen so far, each starting at one.
+pub(crate) struct Model {
+    symbols: u32,
+    counts: [u32; MAX_SYMBOLS],
+    /// A Fenwick tree of `counts`, for their prefix sums.
+    sums: [u32; MAX_SYMBOLS],
+    total: u32,
+}
+
+impl Model {
+    pub(crate) fn new(symbols: u32) -> Self {
+        assert!(symbols as usize <= MAX_SYMBOLS);
+        let mut ret = Self {
+            symbols,
+            counts: [0; MAX_SYMBOLS],
+            sums: [0; MAX_SYMBOLS],
+            total: 0,
+        };
+        for symbol in 0..symbols {
+            ret.update(symbol);
+        }
+        ret
+    }
+
+    pub(crate) fn update(&mut self, symbol: u32) {
+        self.total += 1;
+        self.counts[symbol as usize] += 1;
+        let mut i = symbol as usize + 1;
+        while i <= self.symbols as usize {
+            self.sums[i - 1] += 1;
+            i += i & i.wrapping_neg();
+        }
+    }
+
+    /// The bounds of `symbol`'s share of the probability.
+    fn probability(&self, symbol: u32) -> (f64, f64) {
+        let mut high = 0;
+        let mut i = symbol as usize + 1;
+        while i > 0 {
+            high += self.sums[i - 1];
+            i &= i - 1;
+        }
+        let low = high - self.counts[symbol as usize];
+        let total = self.total as f64;
+        (low as f64 / total, high as f64 / total)
+    }
+}
+
+/// The interval being narrowed, scaled to `precision` bits.
+struct Range {
+    low: u64,
+    high: u64,
+    half: u64,
+    quarter: u64,
+    three_quarters: u64,
+}
+
+impl Range {
+    fn new(precision: u64) -> Self {
+        let high = 1 << precision;
+        Self {
+            low: 0,
+            high,
+            half: high / 2,
+            quarter: high / 4,
+            three_quarters: high / 4 * 3,
+        }
+    }
+
+    fn in_bottom_half(&self) -> bool {
+        self.high < self.half
+    }
+
+    fn in_upper_half(&self) -> bool {
+        self.low > self.half
+    }
+
+    fn in_middle_half(&self) -> bool {
+        self.low > self.quarter && self.high < self.three_quarters
+    }
+
+    fn scale_bottom_half(&mut self) {
+        self.low <<= 1;
+        self.high <<= 1;
+    }
+
+    fn scale_upper_half(&mut self) {
+        self.low = (self.low - self.half) << 1;
+        self.high = (self.high - self.half) << 1;
+    }
+
+    fn scale_middle_half(&mut self) {
+        self.low = (self.low - self.quarter) << 1;
+        self.high = (self.high - self.quarter) << 1;
+    }
+
+    fn narrow(&self, symbol: u32, model: &Model) -> (u64, u64) {
+        let width = self.high - self.low;
+        let (low, high) = model.probability(symbol);
+        (
+            self.low + (width as f64 * low) as u64,
+            self.low + (width as f64 * high) as u64,
+        )
+    }
+}
+
+pub(crate) struct Encoder<W> {
+    range: Range,
+    pending: u32,
+    writer: W,
+    byte: u8,
+    bits: u32,
+}
+
+impl<W: Write> Encoder<W> {
+    pub(crate) fn new(precision: u64, writer: W) -> Self {
+        Self {
+            range: Range::new(precision),
+            pending: 0,
+            writer,
+            byte: 0,
+            bits: 0,
+        }
+    }
+
+    pub(crate) fn encode(&mut self, symbol: u32, model: &Model) -> io::Result<()> {
+        (self.range.low, self.range.high) = self.range.narrow(symbol, model);
+        loop {
+            if self.range.in_bottom_half() {
+                self.range.scale_bottom_half();
+                self.emit(false)?;
+            } else if self.range.in_upper_half() {
+                self.range.scale_upper_half();
+                self.emit(true)?;
+            } else {
+                break;
+            }
+        }
+        while self.range.in_middle_half() {
+            self.pending += 1;
+            self.range.scale_middle_half();
+        }
+        Ok(())
+    }
+
+    /// Writes the bits that tell the final interval apart, padded with zeros
+    /// to a whole byte.
+    pub(crate) fn finish(mut self) -> io::Result<()> {
+        self.pending += 1;
+        self.emit(self.range.low > self.range.quarter)?;
+        while self.bits != 0 {
+            self.write_bit(false)?;
+        }
+        Ok(())
+    }
+
+    fn emit(&mut self, bit: bool) -> io::Result<()> {
+        self.write_bit(bit)?;
+        while self.pending > 0 {
+            self.write_bit(!bit)?;
+            self.pending -= 1;
+        }
+        Ok(())
+    }
+
+    fn write_bit(&mut self, bit: bool) -> io::Result<()> {
+        self.byte = self.byte << 1 | bit as u8;
+        self.bits += 1;
+        if self.bits == 8 {
+            self.writer.write_all(&[self.byte])?;
+            self.byte = 0;
+            self.bits = 0;
+        }
+        Ok(())
+    }
+}
+
+pub(crate) struct Decoder<Rd> {
+    range: Range,
+    precision: u64,
+    /// How many more zero bits may be read past the end of the input.
+    padding: u64,
+    value: Option<u64>,
+    reader: Rd,
+    byte: u8,
+    bits: u32,
+}
+
+impl<Rd: Read> Decoder<Rd> {
+    pub(crate) fn new(precision: u64, reader: Rd) -> Self {
+        Self {
+            range: Range::new(precision),
+            precision,
+            padding: precision,
+            value: None,
+            reader,
+            byte: 0,
+            bits: 0,
+        }
+    }
+
+    pub(crate) fn decode(&mut self, model: &Model) -> Result<u32, DecompressError> {
+        let mut value = match self.value {
+            Some(value) => value,
+            None => {
+                let mut value = 0;
+                for _ in 0..self.precision {
+                    value = value << 1 | self.read_bit()?;
+                }
+                value
+            }
+        };
+
+        let (mut low, mut high) = (0, model.symbols);
+        let (symbol, range) = loop {
+            let mid = (low + high) / 2;
+            if mid >= model.symbols {
+                return Err(DecompressError::Invalid);
+            }
+            let range = self.range.narrow(mid, model);
+            if range.0 <= value && value < range.1 {
+                break (mid, range);
+            } else if value >= range.1 {
+                low = mid + 1;
+            } else {
+                high = mid.checked_sub(1).ok_or(DecompressError::Invalid)?;
+            }
+        };
+
+        (self.range.low, self.range.high) = range;
+        loop {
+            if self.range.in_bottom_half() {
+                self.range.scale_bottom_half();
+                value = value << 1 | self.read_bit()?;
+            } else if self.range.in_upper_half() {
+                self.range.scale_upper_half();
+                value = (value - self.range.half) << 1 | self.read_bit()?;
+            } else {
+                break;
+            }
+        }
+        while self.range.in_middle_half() {
+            self.range.scale_middle_half();
+            value = (value - self.range.quarter) << 1 | self.read_bit()?;
+        }
+        self.value = Some(value);
+        Ok(symbol)
+    }
+
+    /// The next bit, most significant first, or a zero for each of the first
+    /// `precision` bits past the end of the input.
+    fn read_bit(&mut self) -> Result<u64, DecompressError> {
+        if self.bits == 0 {
+            match self.reader.read_byte() {
+                Some(byte) => {
+                    self.byte = byte;
+                    self.bits = 8;
+                }
+                None => {
+                    self.padding = self
+                        .padding
+                        .checked_sub(1)
+                        .ok_or(DecompressError::Truncated)?;
+                    return Ok(0);
+                }
+            }
+        }
+        self.bits -= 1;
+        Ok((self.byte >> self.bits & 1) as u64)
+    }
+}
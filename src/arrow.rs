@@ -0,0 +1,132 @@
+//! Storing [`HyperLogLog`]s in Arrow binary arrays, and so in Parquet
+//! files, with the bytes of the binary serde impls.
+//!
+//! Columns are tagged with the [`HyperLogLogType`] extension type, which
+//! records the precision, so readers can check it before decoding.
+
+use crate::{DecompressError, HyperLogLog, Registers};
+use arrow_array::builder::BinaryBuilder;
+use arrow_array::BinaryArray;
+use arrow_schema::extension::ExtensionType;
+use arrow_schema::{ArrowError, DataType, Field};
+use std::format;
+use std::string::String;
+
+/// The Arrow extension type of a binary column of [`HyperLogLog`]s.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct HyperLogLogType {
+    precision: u8,
+}
+
+impl HyperLogLogType {
+    pub fn new(precision: u8) -> Self {
+        Self { precision }
+    }
+
+    /// [`Registers::PRECISION`] of the column's sketches.
+    pub fn precision(&self) -> u8 {
+        self.precision
+    }
+}
+
+impl ExtensionType for HyperLogLogType {
+    const NAME: &'static str = "hyperloglog.sketch";
+
+    type Metadata = u8;
+
+    fn metadata(&self) -> &u8 {
+        &self.precision
+    }
+
+    fn serialize_metadata(&self) -> Option<String> {
+        Some(format!("{{\"precision\":{}}}", self.precision))
+    }
+
+    fn deserialize_metadata(metadata: Option<&str>) -> Result<u8, ArrowError> {
+        metadata
+            .and_then(|m| m.strip_prefix("{\"precision\":")?.strip_suffix('}'))
+            .and_then(|precision| precision.parse().ok())
+            .ok_or_else(|| {
+                ArrowError::InvalidArgumentError("hyperloglog invalid extension metadata".into())
+            })
+    }
+
+    fn supports_data_type(&self, data_type: &DataType) -> Result<(), ArrowError> {
+        match data_type {
+            DataType::Binary => Ok(()),
+            _ => Err(ArrowError::InvalidArgumentError(format!(
+                "hyperloglog unsupported data type {data_type}"
+            ))),
+        }
+    }
+
+    fn try_new(data_type: &DataType, precision: u8) -> Result<Self, ArrowError> {
+        let ret = Self::new(precision);
+        ret.supports_data_type(data_type)?;
+        Ok(ret)
+    }
+}
+
+/// A binary field of [`HyperLogLog<R>`]s, tagged with [`HyperLogLogType`].
+pub fn field<R: Registers>(name: impl Into<String>, nullable: bool) -> Field {
+    Field::new(name, DataType::Binary, nullable)
+        .with_extension_type(HyperLogLogType::new(R::PRECISION))
+}
+
+/// Checks that `field` was created by [`field`] for the same precision.
+pub fn check_field<R: Registers>(field: &Field) -> Result<(), ArrowError> {
+    let precision = field.try_extension_type::<HyperLogLogType>()?.precision;
+    if precision != R::PRECISION {
+        return Err(ArrowError::InvalidArgumentError(format!(
+            "hyperloglog precision {precision} differs"
+        )));
+    }
+    Ok(())
+}
+
+/// Writes sketches to a binary array, with `None` as null.
+pub fn to_array<'a, R: Registers + 'a>(
+    sketches: impl IntoIterator<Item = Option<&'a HyperLogLog<R>>>,
+) -> BinaryArray {
+    let mut builder = BinaryBuilder::new();
+    for sketch in sketches {
+        match sketch {
+            Some(sketch) => append(&mut builder, sketch),
+            None => builder.append_null(),
+        }
+    }
+    builder.finish()
+}
+
+/// Reads the sketches of a binary array, with null as `None`.
+pub fn from_array<R: Registers>(
+    array: &BinaryArray,
+) -> impl ExactSizeIterator<Item = Result<Option<HyperLogLog<R>>, DecompressError>> + '_ {
+    array.iter().map(|value| {
+        value
+            .map(|value| {
+                let mut ret = HyperLogLog::<R>::default();
+                ret.0.decompress(value)?;
+                Ok(ret)
+            })
+            .transpose()
+    })
+}
+
+/// Merges the sketches of a binary array, skipping nulls.
+pub fn merge_array<R: Registers>(array: &BinaryArray) -> Result<HyperLogLog<R>, DecompressError> {
+    let mut ret = HyperLogLog::<R>::default();
+    let mut sketch = R::zero();
+    for value in array.iter().flatten() {
+        sketch.decompress(value)?;
+        ret.0.merge(&sketch);
+    }
+    Ok(ret)
+}
+
+/// Appends a sketch to `builder`, for building an array a row at a time.
+pub fn append<R: Registers>(builder: &mut BinaryBuilder, sketch: &HyperLogLog<R>) {
+    // Written straight into the value buffer, and then finished.
+    sketch.write_compressed(&mut *builder).unwrap();
+    builder.append_value([]);
+}
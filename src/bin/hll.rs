@@ -0,0 +1,323 @@
+//! Estimates the number of distinct lines, or delimited fields, of files too
+//! large for `sort | uniq | wc -l`, and works with the sketches it writes.
+//!
+//! Lines are counted as bytes, without the `\n`, like
+//! [`HyperLogLog::insert`] of a `&[u8]`.
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use hyperloglog::{HyperLogLog, Registers};
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+#[derive(Parser)]
+#[command(
+    name = "hll",
+    version,
+    about = "Estimates the number of distinct lines"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Counts the distinct lines, or fields, of files or stdin.
+    Count {
+        #[command(flatten)]
+        sketch: SketchArgs,
+        /// Count the fields separated by this byte instead of whole lines.
+        #[arg(short, long, value_parser = parse_byte)]
+        delimiter: Option<u8>,
+        /// The field to count, starting from 1, like `cut -f`.
+        #[arg(short, long, default_value_t = NonZeroUsize::MIN, requires = "delimiter")]
+        field: NonZeroUsize,
+        /// Also write the sketch to this file.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Files to read, or stdin if there are none.
+        files: Vec<PathBuf>,
+    },
+    /// Counts the distinct items of sketch files together.
+    Merge {
+        #[command(flatten)]
+        sketch: SketchArgs,
+        /// Also write the merged sketch to this file.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        #[arg(required = true)]
+        sketches: Vec<PathBuf>,
+    },
+    /// Prints the precision, zero registers, estimator, and histogram of
+    /// register values of a sketch file, or stdin.
+    Inspect {
+        #[command(flatten)]
+        sketch: SketchArgs,
+        sketch_file: Option<PathBuf>,
+    },
+    /// Re-encodes a sketch file, or stdin, to stdout.
+    Convert {
+        #[command(flatten)]
+        sketch: SketchArgs,
+        /// The encoding to write.
+        #[arg(short, long, value_enum)]
+        to: Encoding,
+        sketch_file: Option<PathBuf>,
+    },
+}
+
+#[derive(Args)]
+struct SketchArgs {
+    /// Sketches have `2^precision` registers. Sketch files don't record it,
+    /// so it must match the one they were written with.
+    #[arg(short, long, default_value_t = 14, value_parser = clap::value_parser!(u8).range(4..=18))]
+    precision: u8,
+    /// The encoding of sketch files.
+    #[arg(short, long, value_enum, default_value_t = Encoding::Binary)]
+    encoding: Encoding,
+}
+
+/// The encodings of sketch files.
+#[derive(Copy, Clone, ValueEnum)]
+enum Encoding {
+    /// That of the binary serde impls. Reads checked ones too.
+    Binary,
+    /// Binary, with a CRC-32C checksum.
+    Checked,
+    /// That of the human-readable serde impls.
+    Base64,
+    /// The canonical encoding, which is the same for the same registers.
+    Canonical,
+}
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+/// Calls a function generic over [`Registers`] with the ones of a precision
+/// in `4..=18`.
+macro_rules! with_precision {
+    ($precision:expr, $f:ident($($arg:expr),*)) => {
+        match $precision {
+            4 => $f::<[u8; 16]>($($arg),*),
+            5 => $f::<[u8; 32]>($($arg),*),
+            6 => $f::<[u8; 64]>($($arg),*),
+            7 => $f::<[u8; 128]>($($arg),*),
+            8 => $f::<[u8; 256]>($($arg),*),
+            9 => $f::<[u8; 512]>($($arg),*),
+            10 => $f::<[u8; 1024]>($($arg),*),
+            11 => $f::<[u8; 2048]>($($arg),*),
+            12 => $f::<[u8; 4096]>($($arg),*),
+            13 => $f::<[u8; 8192]>($($arg),*),
+            14 => $f::<[u8; 16384]>($($arg),*),
+            15 => $f::<[u8; 32768]>($($arg),*),
+            16 => $f::<[u8; 65536]>($($arg),*),
+            17 => $f::<[u8; 131072]>($($arg),*),
+            18 => $f::<[u8; 262144]>($($arg),*),
+            precision => unreachable!("precision {precision}"),
+        }
+    };
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse().command) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("hll: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(command: Command) -> Result<()> {
+    match command {
+        Command::Count {
+            sketch,
+            delimiter,
+            field,
+            output,
+            files,
+        } => {
+            let field = delimiter.map(|delimiter| (delimiter, field));
+            with_precision!(
+                sketch.precision,
+                count(sketch.encoding, field, output, files)
+            )
+        }
+        Command::Merge {
+            sketch,
+            output,
+            sketches,
+        } => with_precision!(sketch.precision, merge(sketch.encoding, output, sketches)),
+        Command::Inspect {
+            sketch,
+            sketch_file,
+        } => with_precision!(sketch.precision, inspect(sketch.encoding, sketch_file)),
+        Command::Convert {
+            sketch,
+            to,
+            sketch_file,
+        } => with_precision!(sketch.precision, convert(sketch.encoding, to, sketch_file)),
+    }
+}
+
+fn count<R: Registers>(
+    encoding: Encoding,
+    field: Option<(u8, NonZeroUsize)>,
+    output: Option<PathBuf>,
+    files: Vec<PathBuf>,
+) -> Result<()> {
+    let mut hll = HyperLogLog::<R>::default();
+    if files.is_empty() {
+        count_lines(&mut hll, io::stdin().lock(), field)?;
+    } else {
+        for path in &files {
+            let file = File::open(path).map_err(|error| in_file(path, error))?;
+            count_lines(&mut hll, BufReader::new(file), field)
+                .map_err(|error| in_file(path, error))?;
+        }
+    }
+    finish(&hll, encoding, output)
+}
+
+/// Counts each line of `reader`, or its `field.1`th field delimited by
+/// `field.0`, skipping lines with fewer fields.
+fn count_lines<R: Registers>(
+    hll: &mut HyperLogLog<R>,
+    mut reader: impl BufRead,
+    field: Option<(u8, NonZeroUsize)>,
+) -> io::Result<()> {
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            return Ok(());
+        }
+        let line = line.strip_suffix(b"\n").unwrap_or(&line);
+        match field {
+            None => hll.insert(&line),
+            Some((delimiter, field)) => {
+                if let Some(item) = line.split(|&b| b == delimiter).nth(field.get() - 1) {
+                    hll.insert(&item);
+                }
+            }
+        }
+    }
+}
+
+fn merge<R: Registers>(
+    encoding: Encoding,
+    output: Option<PathBuf>,
+    sketches: Vec<PathBuf>,
+) -> Result<()> {
+    let mut hll = HyperLogLog::<R>::default();
+    for path in &sketches {
+        hll.merge(&read(Some(path), encoding)?);
+    }
+    finish(&hll, encoding, output)
+}
+
+/// Prints the cardinality, and writes the sketch to `output`, if any.
+fn finish<R: Registers>(
+    hll: &HyperLogLog<R>,
+    encoding: Encoding,
+    output: Option<PathBuf>,
+) -> Result<()> {
+    if let Some(path) = output {
+        File::create(&path)
+            .and_then(|mut file| file.write_all(&encode(hll, encoding)))
+            .map_err(|error| in_file(&path, error))?;
+    }
+    println!("{}", hll.cardinality());
+    Ok(())
+}
+
+fn inspect<R: Registers>(encoding: Encoding, path: Option<PathBuf>) -> Result<()> {
+    let hll = read::<R>(path.as_deref(), encoding)?;
+    let stats = hll.stats();
+    let mut stdout = io::stdout().lock();
+    writeln!(stdout, "precision: {}", R::PRECISION)?;
+    writeln!(stdout, "registers: {}", R::REGISTERS)?;
+    writeln!(stdout, "zero registers: {}", stats.zero_registers)?;
+    writeln!(stdout, "estimator: {:?}", stats.estimator)?;
+    writeln!(stdout, "raw estimate: {}", stats.raw_estimate)?;
+    writeln!(stdout, "bias: {}", stats.bias)?;
+    writeln!(stdout, "cardinality: {}", hll.cardinality())?;
+    writeln!(stdout, "histogram:")?;
+    for (value, &count) in stats.histogram.iter().enumerate() {
+        if count > 0 {
+            writeln!(stdout, "  {value}: {count}")?;
+        }
+    }
+    Ok(())
+}
+
+fn convert<R: Registers>(from: Encoding, to: Encoding, path: Option<PathBuf>) -> Result<()> {
+    let hll = read::<R>(path.as_deref(), from)?;
+    io::stdout().lock().write_all(&encode(&hll, to))?;
+    Ok(())
+}
+
+/// Reads a sketch from `path`, or stdin if it's `None`.
+fn read<R: Registers>(path: Option<&Path>, encoding: Encoding) -> Result<HyperLogLog<R>> {
+    let mut bytes = Vec::new();
+    match path {
+        Some(path) => File::open(path)
+            .and_then(|mut file| file.read_to_end(&mut bytes))
+            .map_err(|error| in_file(path, error))?,
+        None => io::stdin().lock().read_to_end(&mut bytes)?,
+    };
+    let in_sketch = |error: &dyn Error| match path {
+        Some(path) => format!("{}: {error}", path.display()),
+        None => format!("stdin: {error}"),
+    };
+    match encoding {
+        Encoding::Binary | Encoding::Checked => {
+            HyperLogLog::decompress(&bytes).map_err(|error| in_sketch(&error).into())
+        }
+        Encoding::Base64 => {
+            let text = std::str::from_utf8(&bytes).map_err(|error| in_sketch(&error))?;
+            HyperLogLog::from_base64(text.trim_end()).map_err(|error| in_sketch(&error).into())
+        }
+        Encoding::Canonical => {
+            HyperLogLog::from_canonical(&bytes).map_err(|error| in_sketch(&error).into())
+        }
+    }
+}
+
+fn encode<R: Registers>(hll: &HyperLogLog<R>, encoding: Encoding) -> Vec<u8> {
+    match encoding {
+        Encoding::Binary => {
+            let mut bytes = Vec::new();
+            hll.compress_into(&mut bytes);
+            bytes
+        }
+        Encoding::Checked => {
+            let mut bytes = Vec::new();
+            hll.compress_checked_into(hyperloglog::checked::Checksum::Crc32c, &mut bytes);
+            bytes
+        }
+        Encoding::Base64 => {
+            let mut text = String::new();
+            hll.write_base64(&mut text).unwrap();
+            text.push('\n');
+            text.into_bytes()
+        }
+        Encoding::Canonical => hll.to_canonical(),
+    }
+}
+
+fn in_file(path: &Path, error: impl Error) -> Box<dyn Error> {
+    format!("{}: {error}", path.display()).into()
+}
+
+/// Parses a delimiter, which is a single byte, or `\t`.
+fn parse_byte(delimiter: &str) -> std::result::Result<u8, String> {
+    match delimiter.as_bytes() {
+        [byte] => Ok(*byte),
+        b"\\t" => Ok(b'\t'),
+        _ => Err("expected a single byte, or \\t".into()),
+    }
+}
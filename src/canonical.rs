@@ -0,0 +1,165 @@
+//! A canonical encoding, whose bytes are a function of the precision and the
+//! registers alone, for hashing or comparing sketches by their bytes.
+//!
+//! Unlike the serde encoding, which may change between versions as long as
+//! old bytes still decode, this encoding is fixed:
+//!
+//! - A version byte, 1.
+//! - [`Registers::PRECISION`].
+//! - A tag byte: 0 for dense, 1 for sparse.
+//! - Dense: every register as 6 bits, packed starting from the least
+//!   significant bit of the first byte.
+//! - Sparse: for each non-zero register, the varint of the number of zero
+//!   registers since the previous non-zero one, shifted left by 6, and or-ed
+//!   with the value.
+//!
+//! Sparse is used if and only if it is strictly shorter. Anything else, such
+//! as a dense encoding that should have been sparse or a varint with
+//! trailing zero bytes, is rejected when reading.
+
+use crate::{sparse_pairs, varint_len, HyperLogLog, Registers};
+use alloc::vec::Vec;
+use core::fmt;
+
+/// An error reading a canonical encoding.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CanonicalError {
+    /// Missing header, or an unknown version or tag.
+    InvalidHeader,
+    /// The precision differs from [`Registers::PRECISION`].
+    PrecisionMismatch(u8),
+    /// Registers that are truncated, out of range, or not canonically
+    /// encoded.
+    InvalidRegisters,
+}
+
+impl fmt::Display for CanonicalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidHeader => f.write_str("canonical hyperloglog invalid header"),
+            Self::PrecisionMismatch(precision) => {
+                write!(f, "canonical hyperloglog precision {precision} differs")
+            }
+            Self::InvalidRegisters => f.write_str("canonical hyperloglog invalid registers"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for CanonicalError {}
+
+const VERSION: u8 = 1;
+const DENSE: u8 = 0;
+const SPARSE: u8 = 1;
+const HEADER_LEN: usize = 3;
+const REGISTER_BITS: usize = 6;
+
+impl<R: Registers> HyperLogLog<R> {
+    /// Writes the canonical encoding.
+    pub fn to_canonical(&self) -> Vec<u8> {
+        let registers = self.0.registers();
+        let dense_len = HEADER_LEN + dense_len(registers.len());
+        let sparse_len = HEADER_LEN + sparse_pairs(registers).map(varint_len).sum::<usize>();
+        let sparse = sparse_len < dense_len;
+
+        let mut ret = Vec::with_capacity(dense_len.min(sparse_len));
+        ret.extend_from_slice(&[VERSION, R::PRECISION, if sparse { SPARSE } else { DENSE }]);
+        if sparse {
+            for mut pair in sparse_pairs(registers) {
+                while pair >= 0x80 {
+                    ret.push(pair as u8 | 0x80);
+                    pair >>= 7;
+                }
+                ret.push(pair as u8);
+            }
+        } else {
+            let mut bits = 0u32;
+            let mut len = 0;
+            for &value in registers {
+                bits |= (value as u32) << len;
+                len += REGISTER_BITS;
+                while len >= 8 {
+                    ret.push(bits as u8);
+                    bits >>= 8;
+                    len -= 8;
+                }
+            }
+        }
+        ret
+    }
+
+    /// Reads the canonical encoding, rejecting any other encoding of the
+    /// same registers.
+    pub fn from_canonical(bytes: &[u8]) -> Result<Self, CanonicalError> {
+        let &[version, precision, tag, ref data @ ..] = bytes else {
+            return Err(CanonicalError::InvalidHeader);
+        };
+        if version != VERSION || !matches!(tag, DENSE | SPARSE) {
+            return Err(CanonicalError::InvalidHeader);
+        }
+        if precision != R::PRECISION {
+            return Err(CanonicalError::PrecisionMismatch(precision));
+        }
+
+        let mut ret = Self::default();
+        let registers = ret.0.registers_mut();
+        if tag == DENSE {
+            if data.len() != dense_len(registers.len()) {
+                return Err(CanonicalError::InvalidRegisters);
+            }
+            for (index, register) in registers.iter_mut().enumerate() {
+                let bit = index * REGISTER_BITS;
+                let bits =
+                    data[bit / 8] as u16 | (*data.get(bit / 8 + 1).unwrap_or(&0) as u16) << 8;
+                *register = (bits >> (bit % 8)) as u8 & ((1 << REGISTER_BITS) - 1);
+            }
+        } else {
+            let mut data = data;
+            let mut next = 0usize;
+            while !data.is_empty() {
+                let pair = read_varint(&mut data).ok_or(CanonicalError::InvalidRegisters)?;
+                let value = (pair & 0x3f) as u8;
+                let index = usize::try_from(pair >> 6)
+                    .ok()
+                    .and_then(|delta| next.checked_add(delta))
+                    .filter(|&index| index < registers.len())
+                    .ok_or(CanonicalError::InvalidRegisters)?;
+                if value == 0 {
+                    return Err(CanonicalError::InvalidRegisters);
+                }
+                registers[index] = value;
+                next = index + 1;
+            }
+        }
+        if registers.iter().any(|&r| r > R::MAX_VALUE) {
+            return Err(CanonicalError::InvalidRegisters);
+        }
+        // Sparse if and only if it is shorter.
+        let sparse_len = sparse_pairs(registers).map(varint_len).sum::<usize>();
+        if (tag == SPARSE) != (sparse_len < dense_len(registers.len())) {
+            return Err(CanonicalError::InvalidRegisters);
+        }
+        Ok(ret)
+    }
+}
+
+fn dense_len(registers: usize) -> usize {
+    (registers * REGISTER_BITS).div_ceil(8)
+}
+
+/// Reads a varint, rejecting any that isn't in its shortest form.
+fn read_varint(bytes: &mut &[u8]) -> Option<u64> {
+    let mut ret = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = bytes.split_first()?;
+        *bytes = rest;
+        if shift == 63 && byte > 1 {
+            return None;
+        }
+        ret |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return (byte != 0 || shift == 0).then_some(ret);
+        }
+    }
+    None
+}
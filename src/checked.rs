@@ -0,0 +1,291 @@
+//! Serialization with a checksum, so corruption, such as a flipped bit in
+//! an arithmetic coded payload, is detected rather than silently decoding
+//! to different registers.
+//!
+//! The checked encoding is `0xff 0x02`, the [`Checksum`] id, the varint
+//! length of the encoding being checked, that encoding, and the checksum of
+//! everything before it, little-endian. Everything that decodes the output
+//! of [`HyperLogLog::compress_into`], including the serde impls, also
+//! decodes and verifies this, returning [`DecompressError::ChecksumMismatch`]
+//! if it doesn't match.
+//!
+//! The [`crc32c`] and [`xxhash64`] modules serialize with a checksum, for
+//! use with `#[serde(with = "...")]`. Like the default, they deserialize
+//! input with or without a checksum, so existing data stays readable.
+
+use crate::io::{self, Read, Write};
+use crate::{crc32c::crc32c as update_crc32c, xxhash::Xxh64};
+use crate::{DecompressError, HyperLogLog, Registers, CHECKED_TAG, TAGGED};
+use alloc::vec::Vec;
+use core::fmt;
+use serde::{Deserializer, Serializer};
+
+/// A checksum algorithm.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Checksum {
+    /// CRC-32C (Castagnoli), 4 bytes.
+    Crc32c = 0,
+    /// XXH64 with a seed of 0, 8 bytes.
+    XxHash64 = 1,
+}
+
+impl Checksum {
+    fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(Self::Crc32c),
+            1 => Some(Self::XxHash64),
+            _ => None,
+        }
+    }
+
+    fn len(self) -> usize {
+        match self {
+            Self::Crc32c => 4,
+            Self::XxHash64 => 8,
+        }
+    }
+}
+
+/// CRC-32C checksums.
+pub mod crc32c {
+    use super::{Checksum, HyperLogLog, Registers};
+    use serde::{Deserializer, Serializer};
+
+    pub fn serialize<R, S>(hll: &HyperLogLog<R>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        R: Registers,
+        S: Serializer,
+    {
+        super::serialize(hll, Checksum::Crc32c, serializer)
+    }
+
+    pub fn deserialize<'de, R, D>(deserializer: D) -> Result<HyperLogLog<R>, D::Error>
+    where
+        R: Registers,
+        D: Deserializer<'de>,
+    {
+        super::deserialize(deserializer)
+    }
+}
+
+/// XXH64 checksums.
+pub mod xxhash64 {
+    use super::{Checksum, HyperLogLog, Registers};
+    use serde::{Deserializer, Serializer};
+
+    pub fn serialize<R, S>(hll: &HyperLogLog<R>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        R: Registers,
+        S: Serializer,
+    {
+        super::serialize(hll, Checksum::XxHash64, serializer)
+    }
+
+    pub fn deserialize<'de, R, D>(deserializer: D) -> Result<HyperLogLog<R>, D::Error>
+    where
+        R: Registers,
+        D: Deserializer<'de>,
+    {
+        super::deserialize(deserializer)
+    }
+}
+
+impl<R: Registers> HyperLogLog<R> {
+    /// Like [`Self::compress_into`], with a checksum.
+    pub fn compress_checked_into(&self, checksum: Checksum, out: &mut Vec<u8>) {
+        let start = out.len();
+        out.extend_from_slice(&[TAGGED, CHECKED_TAG, checksum as u8]);
+        let inner = out.len();
+        self.compress_into(out);
+        // The length goes before what it's the length of.
+        let (varint, len) = varint(out.len() - inner);
+        out.extend_from_slice(&varint[..len]);
+        out[inner..].rotate_right(len);
+
+        let mut state = State::new(checksum);
+        state.update(&out[start..]);
+        out.extend_from_slice(&state.finish()[..checksum.len()]);
+    }
+
+    /// Like [`Self::write_compressed`], with a checksum. The registers are
+    /// encoded twice more, first to measure them.
+    #[cfg(feature = "std")]
+    pub fn write_compressed_checked<W: std::io::Write>(
+        &self,
+        checksum: Checksum,
+        writer: W,
+    ) -> std::io::Result<()> {
+        let mut writer = io::StdWriter::new(writer);
+        let result = self.encode_checked(checksum, &mut writer);
+        writer.finish(result)
+    }
+
+    /// [`Self::write_compressed_checked`], for any [`io::Write`].
+    fn encode_checked<W: Write>(&self, checksum: Checksum, writer: W) -> io::Result<()> {
+        let mut measure = crate::Measure::default();
+        self.encode(&mut measure)?;
+
+        let mut writer = Checker {
+            inner: writer,
+            state: State::new(checksum),
+        };
+        writer.write_all(&[TAGGED, CHECKED_TAG, checksum as u8])?;
+        let (varint, len) = varint(measure.len);
+        writer.write_all(&varint[..len])?;
+        self.encode(&mut writer)?;
+        let Checker { mut inner, state } = writer;
+        inner.write_all(&state.finish()[..checksum.len()])
+    }
+}
+
+/// Decodes the rest of the checked encoding, after its tag.
+pub(crate) fn decompress<Rd: Read>(
+    registers: &mut [u8],
+    max_value: u8,
+    precision: u8,
+    mut reader: Rd,
+) -> Result<(), DecompressError> {
+    let id = reader.read_byte().ok_or(DecompressError::Truncated)?;
+    let checksum = Checksum::from_id(id).ok_or(DecompressError::Invalid)?;
+    let mut checker = Checker {
+        inner: reader,
+        state: State::new(checksum),
+    };
+    checker.state.update(&[TAGGED, CHECKED_TAG, id]);
+    let len = read_varint(&mut checker)?;
+
+    let mut inner = (&mut checker).take(len);
+    let result = crate::decompress_unchecked(registers, max_value, precision, &mut inner);
+    // The checksum covers the whole encoding, even what wasn't needed.
+    io::drain(&mut inner);
+    let complete = inner.limit() == 0;
+
+    let Checker {
+        inner: mut reader,
+        state,
+    } = checker;
+    let mut expected = [0; 8];
+    let expected = &mut expected[..checksum.len()];
+    if !complete || reader.read_exact(expected).is_err() {
+        return Err(DecompressError::Truncated);
+    }
+    if *expected != state.finish()[..checksum.len()] {
+        return Err(DecompressError::ChecksumMismatch);
+    }
+    if reader.read_byte().is_some() {
+        return Err(DecompressError::Invalid);
+    }
+    result
+}
+
+enum State {
+    Crc32c(u32),
+    XxHash64(Xxh64),
+}
+
+impl State {
+    fn new(checksum: Checksum) -> Self {
+        match checksum {
+            Checksum::Crc32c => Self::Crc32c(0),
+            Checksum::XxHash64 => Self::XxHash64(Xxh64::new(0)),
+        }
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        match self {
+            Self::Crc32c(crc) => *crc = update_crc32c(*crc, bytes),
+            Self::XxHash64(state) => state.update(bytes),
+        }
+    }
+
+    /// The checksum, little-endian, padded to 8 bytes.
+    fn finish(&self) -> [u8; 8] {
+        match self {
+            Self::Crc32c(crc) => (*crc as u64).to_le_bytes(),
+            Self::XxHash64(state) => state.finish().to_le_bytes(),
+        }
+    }
+}
+
+/// Checksums what is read from or written to it.
+struct Checker<T> {
+    inner: T,
+    state: State,
+}
+
+impl<Rd: Read> Read for Checker<Rd> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.state.update(&buf[..len]);
+        Ok(len)
+    }
+}
+
+impl<W: Write> Write for Checker<W> {
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.inner.write_all(buf)?;
+        self.state.update(buf);
+        Ok(())
+    }
+}
+
+fn read_varint<Rd: Read>(reader: &mut Rd) -> Result<u64, DecompressError> {
+    let mut ret = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = reader.read_byte().ok_or(DecompressError::Truncated)?;
+        ret |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(ret);
+        }
+    }
+    Err(DecompressError::Invalid)
+}
+
+fn varint(mut value: usize) -> ([u8; 10], usize) {
+    let mut ret = [0; 10];
+    let mut len = 0;
+    while value >= 0x80 {
+        ret[len] = value as u8 | 0x80;
+        value >>= 7;
+        len += 1;
+    }
+    ret[len] = value as u8;
+    (ret, len + 1)
+}
+
+fn serialize<R, S>(
+    hll: &HyperLogLog<R>,
+    checksum: Checksum,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    R: Registers,
+    S: Serializer,
+{
+    struct Base64<'a, R>(&'a HyperLogLog<R>, Checksum);
+    impl<R: Registers> fmt::Display for Base64<'_, R> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            let mut encoder = io::Base64Writer::new(&base64::prelude::BASE64_STANDARD_NO_PAD, f);
+            self.0
+                .encode_checked(self.1, &mut encoder)
+                .map_err(|_| fmt::Error)?;
+            encoder.finish()
+        }
+    }
+
+    if serializer.is_human_readable() {
+        serializer.collect_str(&Base64(hll, checksum))
+    } else {
+        let mut bytes = Vec::new();
+        hll.compress_checked_into(checksum, &mut bytes);
+        serializer.serialize_bytes(&bytes)
+    }
+}
+
+fn deserialize<'de, R, D>(deserializer: D) -> Result<HyperLogLog<R>, D::Error>
+where
+    R: Registers,
+    D: Deserializer<'de>,
+{
+    serde::Deserialize::deserialize(deserializer)
+}
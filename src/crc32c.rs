@@ -0,0 +1,33 @@
+//! CRC-32C (Castagnoli), for checking bytes.
+
+const POLYNOMIAL: u32 = 0x82f63b78;
+
+const TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                crc >> 1 ^ POLYNOMIAL
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// Continues the CRC-32C `crc` of earlier bytes with `bytes`, starting from
+/// 0.
+pub(crate) fn crc32c(crc: u32, bytes: &[u8]) -> u32 {
+    let mut crc = !crc;
+    for &byte in bytes {
+        crc = TABLE[(crc as u8 ^ byte) as usize] ^ crc >> 8;
+    }
+    !crc
+}
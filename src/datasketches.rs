@@ -0,0 +1,353 @@
+//! Conversion to and from the serialized form of Apache DataSketches'
+//! `HllSketch`.
+//!
+//! Items must be inserted with [`MurmurHash3`] to stay compatible with
+//! sketches updated by DataSketches itself. Sketches with a larger `lgK` than
+//! [`Registers::PRECISION`] are folded down on import, like a DataSketches
+//! union would.
+
+pub use crate::murmur3::murmurhash3_x64_128;
+use crate::{HyperLogLog, ItemHasher, Registers};
+use alloc::{vec, vec::Vec};
+use core::fmt;
+
+/// The register width of a serialized `HllSketch`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum HllType {
+    /// 4-bit offsets from the minimum register, with exceptions stored aside.
+    Hll4,
+    /// 6-bit registers.
+    Hll6,
+    /// 8-bit registers.
+    Hll8,
+}
+
+/// An error reading a serialized `HllSketch`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DataSketchesError {
+    /// Not an `HllSketch`, or a serialization version or flags this crate
+    /// doesn't read.
+    InvalidHeader,
+    /// Fewer bytes than the header calls for.
+    Truncated,
+    /// Serialized with a smaller `lgK` than [`Registers::PRECISION`].
+    PrecisionTooLow(u8),
+    /// A register value that doesn't fit in 6 bits, or a missing exception.
+    InvalidRegisters,
+}
+
+impl fmt::Display for DataSketchesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidHeader => f.write_str("datasketches hll invalid header"),
+            Self::Truncated => f.write_str("datasketches hll bytes too short"),
+            Self::PrecisionTooLow(lg_k) => {
+                write!(f, "datasketches hll lgK {lg_k} lower than precision")
+            }
+            Self::InvalidRegisters => f.write_str("datasketches hll invalid registers"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DataSketchesError {}
+
+const SER_VER: u8 = 1;
+const FAMILY_ID: u8 = 7;
+const LIST_PREINTS: u8 = 2;
+const SET_PREINTS: u8 = 3;
+const HLL_PREINTS: u8 = 10;
+const HLL_BYTE_ARR_START: usize = 40;
+
+const BIG_ENDIAN_FLAG: u8 = 1;
+const EMPTY_FLAG: u8 = 4;
+const COMPACT_FLAG: u8 = 8;
+const OUT_OF_ORDER_FLAG: u8 = 16;
+
+const LIST: u8 = 0;
+const SET: u8 = 1;
+const HLL: u8 = 2;
+
+/// `LG_INIT_LIST_SIZE`.
+const LG_INIT_LIST_SIZE: u8 = 3;
+/// `LG_AUX_ARR_INTS`, indexed by `lgK`.
+const LG_AUX_ARR_INTS: [u8; 22] = [
+    0, 2, 2, 2, 2, 2, 2, 3, 3, 3, 4, 4, 5, 5, 6, 7, 8, 9, 10, 11, 12, 13,
+];
+const KEY_BITS_26: u32 = 26;
+const AUX_TOKEN: u8 = 15;
+
+impl<R: Registers> HyperLogLog<R> {
+    /// Reads a serialized `HllSketch` in any mode (list, set or HLL) and of
+    /// any [`HllType`].
+    pub fn from_datasketches(bytes: &[u8]) -> Result<Self, DataSketchesError> {
+        if bytes.len() < 8 {
+            return Err(DataSketchesError::Truncated);
+        }
+        let pre_ints = bytes[0];
+        let lg_k = bytes[3];
+        let lg_arr = bytes[4];
+        let flags = bytes[5];
+        let cur_mode = bytes[7] & 0b11;
+        let hll_type = bytes[7] >> 2 & 0b11;
+        if bytes[1] != SER_VER
+            || bytes[2] != FAMILY_ID
+            || !(4..=21).contains(&lg_k)
+            || lg_arr > lg_k
+            || flags & BIG_ENDIAN_FLAG != 0
+            || hll_type > 2
+        {
+            return Err(DataSketchesError::InvalidHeader);
+        }
+        if lg_k < R::PRECISION {
+            return Err(DataSketchesError::PrecisionTooLow(lg_k));
+        }
+
+        let mut ret = Self::default();
+        if flags & EMPTY_FLAG != 0 {
+            return Ok(ret);
+        }
+        let compact = flags & COMPACT_FLAG != 0;
+
+        match (cur_mode, pre_ints) {
+            (LIST, LIST_PREINTS) => {
+                let count = if compact {
+                    bytes[6] as usize
+                } else {
+                    1 << lg_arr
+                };
+                for coupon in read_ints(bytes, 8, count)? {
+                    ret.update_coupon(coupon)?;
+                }
+            }
+            (SET, SET_PREINTS) => {
+                let count = if compact {
+                    read_ints(bytes, 8, 1)?.next().unwrap() as usize
+                } else {
+                    1 << lg_arr
+                };
+                for coupon in read_ints(bytes, 12, count)? {
+                    ret.update_coupon(coupon)?;
+                }
+            }
+            (HLL, HLL_PREINTS) => {
+                let k = 1usize << lg_k;
+                let cur_min = bytes[6];
+                let start = HLL_BYTE_ARR_START;
+                match hll_type {
+                    0 => {
+                        let nibbles = bytes
+                            .get(start..start + k / 2)
+                            .ok_or(DataSketchesError::Truncated)?;
+                        let aux_count = read_ints(bytes, 36, 1)?.next().unwrap() as usize;
+                        let aux_len = match (aux_count, compact) {
+                            (0, _) => 0,
+                            (_, true) => aux_count,
+                            (_, false) => 1 << lg_arr,
+                        };
+                        let aux = read_ints(bytes, start + k / 2, aux_len)?;
+                        let mut exceptions = 0;
+                        for (slot, &byte) in nibbles.iter().enumerate() {
+                            for (slot, nibble) in
+                                [(slot * 2, byte & 0xf), (slot * 2 + 1, byte >> 4)]
+                            {
+                                if nibble == AUX_TOKEN {
+                                    exceptions += 1;
+                                } else {
+                                    ret.update_register(slot, cur_min.saturating_add(nibble))?;
+                                }
+                            }
+                        }
+                        let mut found = 0;
+                        for pair in aux.filter(|&pair| pair != 0) {
+                            found += 1;
+                            ret.update_coupon(pair)?;
+                        }
+                        if found != exceptions {
+                            return Err(DataSketchesError::InvalidRegisters);
+                        }
+                    }
+                    1 => {
+                        let packed = bytes
+                            .get(start..start + k * 3 / 4 + 1)
+                            .ok_or(DataSketchesError::Truncated)?;
+                        for slot in 0..k {
+                            let bit = slot * 6;
+                            let pair = u16::from_le_bytes([packed[bit / 8], packed[bit / 8 + 1]]);
+                            ret.update_register(slot, (pair >> (bit % 8)) as u8 & 0x3f)?;
+                        }
+                    }
+                    _ => {
+                        let registers = bytes
+                            .get(start..start + k)
+                            .ok_or(DataSketchesError::Truncated)?;
+                        for (slot, &value) in registers.iter().enumerate() {
+                            ret.update_register(slot, value)?;
+                        }
+                    }
+                }
+            }
+            _ => return Err(DataSketchesError::InvalidHeader),
+        }
+        Ok(ret)
+    }
+
+    /// Writes a compact, HLL-mode `HllSketch` with `lgK` equal to
+    /// [`Registers::PRECISION`].
+    ///
+    /// The sketch is flagged as out of order (like the result of a union), so
+    /// DataSketches estimates it from the registers alone.
+    pub fn to_datasketches(&self, hll_type: HllType) -> Vec<u8> {
+        let registers = self.0.registers();
+        let lg_k = R::PRECISION;
+        let k = R::REGISTERS;
+        let type_bits = match hll_type {
+            HllType::Hll4 => 0,
+            HllType::Hll6 => 1,
+            HllType::Hll8 => 2,
+        } << 2;
+
+        if registers.iter().all(|&r| r == 0) {
+            return vec![
+                LIST_PREINTS,
+                SER_VER,
+                FAMILY_ID,
+                lg_k,
+                LG_INIT_LIST_SIZE,
+                EMPTY_FLAG | COMPACT_FLAG,
+                0,
+                LIST | type_bits,
+            ];
+        }
+
+        let cur_min = if hll_type == HllType::Hll4 {
+            registers.iter().copied().min().unwrap()
+        } else {
+            0
+        };
+        let num_at_cur_min = bytecount::count(registers, cur_min) as u32;
+        let (mut kxq0, mut kxq1) = (0.0, 0.0);
+        for &r in registers {
+            if r < 32 {
+                kxq0 += libm::scalbn(1.0, -(r as i32));
+            } else {
+                kxq1 += libm::scalbn(1.0, -(r as i32));
+            }
+        }
+
+        let mut ret = vec![
+            HLL_PREINTS,
+            SER_VER,
+            FAMILY_ID,
+            lg_k,
+            if hll_type == HllType::Hll4 {
+                LG_AUX_ARR_INTS[lg_k as usize]
+            } else {
+                0
+            },
+            COMPACT_FLAG | OUT_OF_ORDER_FLAG,
+            cur_min,
+            HLL | type_bits,
+        ];
+        ret.extend_from_slice(&(self.cardinality() as f64).to_le_bytes());
+        ret.extend_from_slice(&f64::to_le_bytes(kxq0));
+        ret.extend_from_slice(&f64::to_le_bytes(kxq1));
+        ret.extend_from_slice(&num_at_cur_min.to_le_bytes());
+        let aux_count_offset = ret.len();
+        ret.extend_from_slice(&0u32.to_le_bytes());
+
+        match hll_type {
+            HllType::Hll4 => {
+                let mut aux = Vec::new();
+                for (slot, pair) in registers.chunks_exact(2).enumerate() {
+                    let mut byte = 0;
+                    for (i, &r) in pair.iter().enumerate() {
+                        let mut nibble = r - cur_min;
+                        if nibble >= AUX_TOKEN {
+                            nibble = AUX_TOKEN;
+                            aux.push((r as u32) << KEY_BITS_26 | (slot * 2 + i) as u32);
+                        }
+                        byte |= nibble << (i * 4);
+                    }
+                    ret.push(byte);
+                }
+                ret[aux_count_offset..aux_count_offset + 4]
+                    .copy_from_slice(&(aux.len() as u32).to_le_bytes());
+                for pair in aux {
+                    ret.extend_from_slice(&pair.to_le_bytes());
+                }
+            }
+            HllType::Hll6 => {
+                let start = ret.len();
+                ret.resize(start + k * 3 / 4 + 1, 0);
+                let packed = &mut ret[start..];
+                for (slot, &r) in registers.iter().enumerate() {
+                    let bit = slot * 6;
+                    let bits = (r.min(0x3f) as u16) << (bit % 8);
+                    packed[bit / 8] |= bits as u8;
+                    packed[bit / 8 + 1] |= (bits >> 8) as u8;
+                }
+            }
+            HllType::Hll8 => ret.extend_from_slice(registers),
+        }
+        ret
+    }
+
+    /// Applies a `(value << 26) | slot` coupon or exception pair.
+    fn update_coupon(&mut self, coupon: u32) -> Result<(), DataSketchesError> {
+        if coupon == 0 {
+            // Empty hash table slot.
+            return Ok(());
+        }
+        let slot = (coupon & ((1 << KEY_BITS_26) - 1)) as usize;
+        self.update_register(slot, (coupon >> KEY_BITS_26) as u8)
+    }
+
+    /// Values past [`Registers::MAX_VALUE`], which are too unlikely to
+    /// matter, are saturated.
+    fn update_register(&mut self, slot: usize, value: u8) -> Result<(), DataSketchesError> {
+        if value > 63 {
+            return Err(DataSketchesError::InvalidRegisters);
+        }
+        let register = &mut self.0.registers_mut()[slot & (R::REGISTERS - 1)];
+        *register = (*register).max(value.min(R::MAX_VALUE));
+        Ok(())
+    }
+}
+
+fn read_ints(
+    bytes: &[u8],
+    start: usize,
+    count: usize,
+) -> Result<impl Iterator<Item = u32> + '_, DataSketchesError> {
+    let ints = start
+        .checked_add(count.checked_mul(4).ok_or(DataSketchesError::Truncated)?)
+        .and_then(|end| bytes.get(start..end))
+        .ok_or(DataSketchesError::Truncated)?;
+    Ok(ints
+        .chunks_exact(4)
+        .map(|int| u32::from_le_bytes(int.try_into().unwrap())))
+}
+
+/// Hashes items like `HllSketch::update`: [`murmurhash3_x64_128`] with the
+/// default seed of 9001, register index from the low bits of the first half,
+/// and value from the leading zeros of the second half, saturated at
+/// [`Registers::MAX_VALUE`].
+///
+/// DataSketches hashes `long`s as their little-endian bytes and strings as
+/// UTF-8, and ignores empty items.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct MurmurHash3;
+
+impl ItemHasher for MurmurHash3 {
+    fn register(&self, item: &[u8], precision: u8) -> (usize, u8) {
+        let (h0, h1) = murmurhash3_x64_128(item, 9001);
+        let index = h0 as usize & ((1 << precision) - 1);
+        let value = h1.leading_zeros().min(62) as u8 + 1;
+        (index, value.min(64 - precision + 1))
+    }
+
+    fn ignores(&self, item: &[u8]) -> bool {
+        item.is_empty()
+    }
+}
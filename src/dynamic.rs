@@ -0,0 +1,117 @@
+//! [`HyperLogLog`]s of a precision chosen at runtime, for the bindings to
+//! other languages.
+
+use crate::{DecompressError, HyperLogLog, Registers};
+use alloc::boxed::Box;
+#[cfg(any(feature = "python", feature = "wasm-bindgen"))]
+use alloc::string::String;
+#[cfg(any(feature = "ffi", feature = "python"))]
+use alloc::vec::Vec;
+use core::any::Any;
+
+/// The operations of [`HyperLogLog`] that don't depend on the registers'
+/// type.
+pub(crate) trait Sketch: Any + Send + Sync {
+    fn precision(&self) -> u8;
+    #[cfg(any(feature = "python", feature = "wasm-bindgen"))]
+    fn insert_str(&mut self, item: &str);
+    fn insert_bytes(&mut self, item: &[u8]);
+    #[cfg(feature = "ffi")]
+    fn insert_hash(&mut self, hash: u64);
+    fn cardinality(&self) -> u64;
+    /// Returns `false`, and does nothing, if the precisions differ.
+    fn merge(&mut self, other: &dyn Sketch) -> bool;
+    #[cfg(any(feature = "python", feature = "wasm-bindgen"))]
+    fn clear(&mut self);
+    #[cfg(any(feature = "ffi", feature = "python"))]
+    fn compress_into(&self, out: &mut Vec<u8>);
+    #[cfg(any(feature = "ffi", feature = "python"))]
+    fn decompress(&mut self, bytes: &[u8]) -> Result<(), DecompressError>;
+    #[cfg(any(feature = "python", feature = "wasm-bindgen"))]
+    fn to_base64(&self) -> String;
+    #[cfg(any(feature = "python", feature = "wasm-bindgen"))]
+    fn set_base64(&mut self, base64: &str) -> Result<(), DecompressError>;
+}
+
+impl<R: Registers + Send + Sync + 'static> Sketch for HyperLogLog<R> {
+    fn precision(&self) -> u8 {
+        R::PRECISION
+    }
+
+    #[cfg(any(feature = "python", feature = "wasm-bindgen"))]
+    fn insert_str(&mut self, item: &str) {
+        self.insert(&item);
+    }
+
+    fn insert_bytes(&mut self, item: &[u8]) {
+        self.insert(&item);
+    }
+
+    #[cfg(feature = "ffi")]
+    fn insert_hash(&mut self, hash: u64) {
+        HyperLogLog::insert_hash(self, hash);
+    }
+
+    fn cardinality(&self) -> u64 {
+        HyperLogLog::cardinality(self)
+    }
+
+    fn merge(&mut self, other: &dyn Sketch) -> bool {
+        let other: &dyn Any = other;
+        other
+            .downcast_ref::<Self>()
+            .map(|other| HyperLogLog::merge(self, other))
+            .is_some()
+    }
+
+    #[cfg(any(feature = "python", feature = "wasm-bindgen"))]
+    fn clear(&mut self) {
+        HyperLogLog::clear(self);
+    }
+
+    #[cfg(any(feature = "ffi", feature = "python"))]
+    fn compress_into(&self, out: &mut Vec<u8>) {
+        HyperLogLog::compress_into(self, out);
+    }
+
+    #[cfg(any(feature = "ffi", feature = "python"))]
+    fn decompress(&mut self, bytes: &[u8]) -> Result<(), DecompressError> {
+        self.0.decompress(bytes)
+    }
+
+    #[cfg(any(feature = "python", feature = "wasm-bindgen"))]
+    fn to_base64(&self) -> String {
+        let mut ret = String::new();
+        self.write_base64(&mut ret).unwrap();
+        ret
+    }
+
+    #[cfg(any(feature = "python", feature = "wasm-bindgen"))]
+    fn set_base64(&mut self, base64: &str) -> Result<(), DecompressError> {
+        *self = Self::from_base64(base64)?;
+        Ok(())
+    }
+}
+
+/// An empty sketch with `2^precision` registers, or `None` if `precision`
+/// isn't in `4..=18`.
+pub(crate) fn empty(precision: u8) -> Option<Box<dyn Sketch>> {
+    Some(match precision {
+        4 => Box::new(HyperLogLog::<[u8; 16]>::default()),
+        5 => Box::new(HyperLogLog::<[u8; 32]>::default()),
+        6 => Box::new(HyperLogLog::<[u8; 64]>::default()),
+        7 => Box::new(HyperLogLog::<[u8; 128]>::default()),
+        8 => Box::new(HyperLogLog::<[u8; 256]>::default()),
+        9 => Box::new(HyperLogLog::<[u8; 512]>::default()),
+        10 => Box::new(HyperLogLog::<[u8; 1024]>::default()),
+        11 => Box::new(HyperLogLog::<[u8; 2048]>::default()),
+        12 => Box::new(HyperLogLog::<[u8; 4096]>::default()),
+        13 => Box::new(HyperLogLog::<[u8; 8192]>::default()),
+        14 => Box::new(HyperLogLog::<[u8; 16384]>::default()),
+        15 => Box::new(HyperLogLog::<[u8; 32768]>::default()),
+        16 => Box::new(HyperLogLog::<[u8; 65536]>::default()),
+        17 => Box::new(HyperLogLog::<[u8; 131072]>::default()),
+        18 => Box::new(HyperLogLog::<[u8; 262144]>::default()),
+        _ => return None,
+    })
+}
@@ -0,0 +1,162 @@
+//! A C API, declared in `include/hyperloglog.h`, for sharing sketches with
+//! services in other languages.
+//!
+//! Sketches are created with a precision, hash items like
+//! [`HyperLogLog::insert`] of a `&[u8]`, and serialize to the bytes of the
+//! binary serde impls, so they merge with, and deserialize as,
+//! [`HyperLogLog`]s of the same precision in Rust.
+//!
+//! Build a library to link against with
+//! `cargo rustc --release --features ffi --crate-type staticlib` (or
+//! `cdylib`).
+//!
+//! ```c
+//! HllSketch *hll = hll_new(14);
+//! hll_insert(hll, (const uint8_t *)"item", 4);
+//! size_t len = hll_serialize(hll, NULL, 0);
+//! uint8_t *bytes = malloc(len);
+//! hll_serialize(hll, bytes, len);
+//! hll_free(hll);
+//! ```
+//!
+//! [`HyperLogLog`]: crate::HyperLogLog
+//! [`HyperLogLog::insert`]: crate::HyperLogLog::insert
+
+use crate::dynamic::{empty, Sketch};
+use std::boxed::Box;
+use std::vec::Vec;
+
+/// A sketch of a precision chosen when it was created.
+pub struct HllSketch(Box<dyn Sketch>);
+
+/// An empty sketch with `2^precision` registers, or null if `precision`
+/// isn't in `4..=18`. Free it with `hll_free`.
+#[no_mangle]
+pub extern "C" fn hll_new(precision: u8) -> *mut HllSketch {
+    match empty(precision) {
+        Some(sketch) => Box::into_raw(Box::new(HllSketch(sketch))),
+        None => std::ptr::null_mut(),
+    }
+}
+
+/// Frees a sketch. Does nothing if `hll` is null.
+///
+/// # Safety
+///
+/// `hll` must be null, or a sketch that hasn't been freed.
+#[no_mangle]
+pub unsafe extern "C" fn hll_free(hll: *mut HllSketch) {
+    if !hll.is_null() {
+        drop(unsafe { Box::from_raw(hll) });
+    }
+}
+
+/// The precision `hll` was created with.
+///
+/// # Safety
+///
+/// `hll` must be a sketch that hasn't been freed.
+#[no_mangle]
+pub unsafe extern "C" fn hll_precision(hll: *const HllSketch) -> u8 {
+    unsafe { &*hll }.0.precision()
+}
+
+/// Counts the `len` bytes at `bytes` if they are distinct.
+///
+/// # Safety
+///
+/// `hll` must be a sketch that hasn't been freed, and `bytes` must point to
+/// `len` readable bytes, or may be null if `len` is 0.
+#[no_mangle]
+pub unsafe extern "C" fn hll_insert(hll: *mut HllSketch, bytes: *const u8, len: usize) {
+    unsafe { &mut *hll }
+        .0
+        .insert_bytes(unsafe { slice(bytes, len) });
+}
+
+/// Counts an item by its 64-bit hash, from a hash other than SipHash, if it
+/// is distinct. The low `precision` bits pick the register, and the rest
+/// are counted.
+///
+/// # Safety
+///
+/// `hll` must be a sketch that hasn't been freed.
+#[no_mangle]
+pub unsafe extern "C" fn hll_insert_hash(hll: *mut HllSketch, hash: u64) {
+    unsafe { &mut *hll }.0.insert_hash(hash);
+}
+
+/// Merges `other` into `hll`, returning false, and doing nothing, if their
+/// precisions differ.
+///
+/// # Safety
+///
+/// `hll` and `other` must be sketches that haven't been freed.
+#[no_mangle]
+pub unsafe extern "C" fn hll_merge(hll: *mut HllSketch, other: *const HllSketch) -> bool {
+    if std::ptr::eq(hll, other) {
+        return true;
+    }
+    unsafe { &mut *hll }.0.merge(&*unsafe { &*other }.0)
+}
+
+/// Estimates the number of distinct items inserted.
+///
+/// # Safety
+///
+/// `hll` must be a sketch that hasn't been freed.
+#[no_mangle]
+pub unsafe extern "C" fn hll_cardinality(hll: *const HllSketch) -> u64 {
+    unsafe { &*hll }.0.cardinality()
+}
+
+/// Returns the length of the serialized sketch, and writes it to `out` if
+/// `capacity` is at least that long, so calling this with a `capacity` of 0
+/// measures it.
+///
+/// # Safety
+///
+/// `hll` must be a sketch that hasn't been freed, and `out` must point to
+/// `capacity` writable bytes, or may be null if `capacity` is 0.
+#[no_mangle]
+pub unsafe extern "C" fn hll_serialize(
+    hll: *const HllSketch,
+    out: *mut u8,
+    capacity: usize,
+) -> usize {
+    let mut bytes = Vec::new();
+    unsafe { &*hll }.0.compress_into(&mut bytes);
+    if bytes.len() <= capacity {
+        unsafe { std::ptr::copy_nonoverlapping(bytes.as_ptr(), out, bytes.len()) };
+    }
+    bytes.len()
+}
+
+/// Decodes the output of `hll_serialize` of a sketch of `precision`, or
+/// returns null if it's invalid. Free it with `hll_free`.
+///
+/// # Safety
+///
+/// `bytes` must point to `len` readable bytes, or may be null if `len` is 0.
+#[no_mangle]
+pub unsafe extern "C" fn hll_deserialize(
+    precision: u8,
+    bytes: *const u8,
+    len: usize,
+) -> *mut HllSketch {
+    let Some(mut sketch) = empty(precision) else {
+        return std::ptr::null_mut();
+    };
+    match sketch.decompress(unsafe { slice(bytes, len) }) {
+        Ok(()) => Box::into_raw(Box::new(HllSketch(sketch))),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+unsafe fn slice<'a>(bytes: *const u8, len: usize) -> &'a [u8] {
+    if len == 0 {
+        &[]
+    } else {
+        unsafe { std::slice::from_raw_parts(bytes, len) }
+    }
+}
@@ -0,0 +1,267 @@
+//! The parts of `std::io` the encodings are written against, so they also
+//! work without `std`. Errors carry nothing, since every caller maps them to
+//! its own error.
+
+use alloc::vec::Vec;
+use base64::Engine;
+use core::fmt;
+
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct Error;
+
+pub(crate) type Result<T> = core::result::Result<T, Error>;
+
+pub(crate) trait Read {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize>;
+
+    fn read_exact(&mut self, mut buf: &mut [u8]) -> Result<()> {
+        while !buf.is_empty() {
+            match self.read(buf)? {
+                0 => return Err(Error),
+                len => buf = &mut buf[len..],
+            }
+        }
+        Ok(())
+    }
+
+    /// The next byte, or `None` at the end of the input or on an error.
+    fn read_byte(&mut self) -> Option<u8> {
+        let mut byte = [0];
+        matches!(self.read(&mut byte), Ok(1)).then_some(byte[0])
+    }
+
+    fn take(self, limit: u64) -> Take<Self>
+    where
+        Self: Sized,
+    {
+        Take {
+            reader: self,
+            limit,
+        }
+    }
+
+    fn chain<Rd: Read>(self, next: Rd) -> Chain<Self, Rd>
+    where
+        Self: Sized,
+    {
+        Chain {
+            first: self,
+            second: next,
+            done_first: false,
+        }
+    }
+}
+
+pub(crate) trait Write {
+    fn write_all(&mut self, buf: &[u8]) -> Result<()>;
+}
+
+impl Read for &[u8] {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let len = buf.len().min(self.len());
+        let (read, rest) = self.split_at(len);
+        buf[..len].copy_from_slice(read);
+        *self = rest;
+        Ok(len)
+    }
+}
+
+impl<Rd: Read + ?Sized> Read for &mut Rd {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        (**self).read(buf)
+    }
+}
+
+impl Write for Vec<u8> {
+    fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+        self.extend_from_slice(buf);
+        Ok(())
+    }
+}
+
+impl<W: Write + ?Sized> Write for &mut W {
+    fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+        (**self).write_all(buf)
+    }
+}
+
+/// Reads at most `limit` bytes.
+pub(crate) struct Take<Rd> {
+    reader: Rd,
+    limit: u64,
+}
+
+impl<Rd> Take<Rd> {
+    /// The number of bytes that may still be read.
+    pub(crate) fn limit(&self) -> u64 {
+        self.limit
+    }
+}
+
+impl<Rd: Read> Read for Take<Rd> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let max = buf
+            .len()
+            .min(usize::try_from(self.limit).unwrap_or(usize::MAX));
+        let len = self.reader.read(&mut buf[..max])?;
+        self.limit -= len as u64;
+        Ok(len)
+    }
+}
+
+/// Reads one reader to its end, and then another.
+pub(crate) struct Chain<A, B> {
+    first: A,
+    second: B,
+    done_first: bool,
+}
+
+impl<A: Read, B: Read> Read for Chain<A, B> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if !self.done_first {
+            match self.first.read(buf)? {
+                0 if !buf.is_empty() => self.done_first = true,
+                len => return Ok(len),
+            }
+        }
+        self.second.read(buf)
+    }
+}
+
+/// Reads until the end of the input or an error.
+pub(crate) fn drain<Rd: Read>(mut reader: Rd) {
+    let mut buf = [0; 64];
+    while matches!(reader.read(&mut buf), Ok(len) if len > 0) {}
+}
+
+/// Writes the base64 of what is written to it to a [`fmt::Write`], once
+/// [`Self::finish`]ed.
+pub(crate) struct Base64Writer<'a, E, W> {
+    engine: &'a E,
+    writer: W,
+    buf: [u8; 48],
+    len: usize,
+}
+
+impl<'a, E: Engine, W: fmt::Write> Base64Writer<'a, E, W> {
+    pub(crate) fn new(engine: &'a E, writer: W) -> Self {
+        Self {
+            engine,
+            writer,
+            buf: [0; 48],
+            len: 0,
+        }
+    }
+
+    /// Writes what's buffered, which is a multiple of 3 bytes unless it's
+    /// the last of the input.
+    fn write_buf(&mut self) -> fmt::Result {
+        let mut encoded = [0; 64];
+        let len = self
+            .engine
+            .encode_slice(&self.buf[..self.len], &mut encoded)
+            .map_err(|_| fmt::Error)?;
+        self.len = 0;
+        // Base64 is ASCII.
+        self.writer
+            .write_str(core::str::from_utf8(&encoded[..len]).unwrap())
+    }
+
+    pub(crate) fn finish(mut self) -> fmt::Result {
+        self.write_buf()
+    }
+}
+
+impl<E: Engine, W: fmt::Write> Write for Base64Writer<'_, E, W> {
+    fn write_all(&mut self, mut buf: &[u8]) -> Result<()> {
+        while !buf.is_empty() {
+            let len = buf.len().min(self.buf.len() - self.len);
+            self.buf[self.len..][..len].copy_from_slice(&buf[..len]);
+            self.len += len;
+            buf = &buf[len..];
+            if self.len == self.buf.len() {
+                self.write_buf().map_err(|_| Error)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Decodes base64 as it is read.
+pub(crate) struct Base64Reader<'a, E> {
+    engine: &'a E,
+    text: &'a [u8],
+    decoded: [u8; 3],
+    start: usize,
+    end: usize,
+}
+
+impl<'a, E: Engine> Base64Reader<'a, E> {
+    pub(crate) fn new(engine: &'a E, text: &'a str) -> Self {
+        Self {
+            engine,
+            text: text.as_bytes(),
+            decoded: [0; 3],
+            start: 0,
+            end: 0,
+        }
+    }
+}
+
+impl<E: Engine> Read for Base64Reader<'_, E> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if self.start == self.end {
+            // Each group of 4 characters decodes on its own, with only the
+            // last allowed to be shorter.
+            let (group, rest) = self.text.split_at(self.text.len().min(4));
+            if group.is_empty() {
+                return Ok(0);
+            }
+            self.text = rest;
+            self.end = self
+                .engine
+                .decode_slice(group, &mut self.decoded)
+                .map_err(|_| Error)?;
+            self.start = 0;
+        }
+        let len = buf.len().min(self.end - self.start);
+        buf[..len].copy_from_slice(&self.decoded[self.start..][..len]);
+        self.start += len;
+        Ok(len)
+    }
+}
+
+/// Adapts a [`std::io::Write`], keeping its error for [`Self::finish`].
+#[cfg(feature = "std")]
+pub(crate) struct StdWriter<W> {
+    writer: W,
+    error: Option<std::io::Error>,
+}
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write> StdWriter<W> {
+    pub(crate) fn new(writer: W) -> Self {
+        Self {
+            writer,
+            error: None,
+        }
+    }
+
+    /// The error of the writer, if `result` failed because of it.
+    pub(crate) fn finish(self, result: Result<()>) -> std::io::Result<()> {
+        result.map_err(|_| {
+            self.error
+                .unwrap_or_else(|| std::io::ErrorKind::Other.into())
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write> Write for StdWriter<W> {
+    fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+        self.writer.write_all(buf).map_err(|error| {
+            self.error = Some(error);
+            Error
+        })
+    }
+}
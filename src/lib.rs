@@ -2,14 +2,72 @@
 
 //! HyperLogLog implementation for Rust
 #![crate_name = "hyperloglog"]
+#![no_std]
 #![warn(non_camel_case_types, non_upper_case_globals, unused_qualifications)]
 #![allow(non_snake_case)]
 #![allow(clippy::unreadable_literal)]
 
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
+
+#[cfg(feature = "airlift")]
+pub mod airlift;
+#[cfg(feature = "serde")]
+mod arithmetic;
+#[cfg(feature = "arrow")]
+pub mod arrow;
+pub mod canonical;
+#[cfg(feature = "serde")]
+pub mod checked;
+#[cfg(feature = "serde")]
+mod crc32c;
+#[cfg(feature = "datasketches")]
+pub mod datasketches;
+#[cfg(any(feature = "ffi", feature = "python", feature = "wasm-bindgen"))]
+mod dynamic;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "serde")]
+mod io;
+pub mod map;
+#[cfg(any(feature = "redis", feature = "streamlib", feature = "zetasketch"))]
+mod murmur2;
+#[cfg(any(feature = "airlift", feature = "datasketches", feature = "postgres"))]
+mod murmur3;
+#[cfg(feature = "postgres")]
+pub mod postgres;
+#[cfg(feature = "prost")]
+pub mod proto;
+#[cfg(feature = "python")]
+pub mod python;
+#[cfg(feature = "redis")]
+pub mod redis;
+pub mod ring;
+pub mod sliding;
+#[cfg(feature = "spark")]
+pub mod spark;
+#[cfg(feature = "store")]
+pub mod store;
+#[cfg(feature = "streamlib")]
+pub mod streamlib;
+pub mod tailcut;
+#[cfg(feature = "serde")]
+pub mod text;
+#[cfg(feature = "wasm-bindgen")]
+pub mod wasm;
 mod weights;
+#[cfg(any(feature = "serde", feature = "spark"))]
+mod xxhash;
+#[cfg(feature = "zetasketch")]
+pub mod zetasketch;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::Debug;
+use core::hash::{Hash, Hasher};
+#[cfg(feature = "serde")]
+use io::{Read, Write};
 use siphasher::sip::SipHasher13;
-use std::fmt::Debug;
-use std::hash::{Hash, Hasher};
 use weights::{BIAS_DATA, RAW_ESTIMATE_DATA, THRESHOLD_DATA};
 
 /// An approximate counter for distinct elements.
@@ -17,7 +75,7 @@ use weights::{BIAS_DATA, RAW_ESTIMATE_DATA, THRESHOLD_DATA};
 pub struct HyperLogLog<R>(R);
 
 impl<R> Debug for HyperLogLog<R> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         f.debug_struct("HyperLogLog").finish_non_exhaustive()
     }
 }
@@ -36,27 +94,136 @@ impl<R: Registers> HyperLogLog<R> {
 
     /// Estimate the number of distinct items inserted.
     pub fn cardinality(&self) -> u64 {
-        let cardinality = self.0.cardinality();
-        debug_assert!(
-            cardinality >= 0.0 && cardinality.is_finite(),
-            "{cardinality}"
-        );
-        cardinality.round() as u64
+        HyperLogLogRef(&self.0).cardinality()
+    }
+
+    /// What [`Self::cardinality`] is estimated from, for explaining it.
+    pub fn stats(&self) -> Stats {
+        HyperLogLogRef(&self.0).stats()
+    }
+
+    /// Count an item by its 64-bit hash, from a hash other than SipHash, if
+    /// it is distinct. The low [`Registers::PRECISION`] bits pick the
+    /// register, and the rest are counted.
+    pub fn insert_hash(&mut self, hash: u64) {
+        insert_hash(&mut self.0, hash);
+    }
+
+    /// Count an item, hashed by `hasher` instead of SipHash, if it is distinct.
+    pub fn insert_with<H: ItemHasher>(&mut self, hasher: &H, item: &[u8]) {
+        if hasher.ignores(item) {
+            return;
+        }
+        let (index, value) = hasher.register(item, R::PRECISION);
+        let register = &mut self.0.registers_mut()[index];
+        *register = (*register).max(value);
     }
 
     pub fn merge(&mut self, other: &Self) {
         self.0.merge(&other.0);
     }
 
+    /// Like [`Self::merge`], for a borrowed counter.
+    pub fn merge_ref(&mut self, other: HyperLogLogRef<'_, R>) {
+        self.0.merge(other.0);
+    }
+
     /// Forgets previous insertions.
     pub fn clear(&mut self) {
         self.0.clear();
     }
+
+    /// The registers, one byte each, like those [`HyperLogLogRef::new`]
+    /// borrows.
+    pub fn registers(&self) -> &[u8] {
+        self.0.registers()
+    }
 }
 
 #[cfg(feature = "serde")]
-thread_local! {
-    static BUFFER: std::cell::RefCell<Vec<u8>> = std::cell::RefCell::default();
+impl<R: Registers> HyperLogLog<R> {
+    /// Appends the encoding used by the binary serde impls to `out`.
+    pub fn compress_into(&self, out: &mut Vec<u8>) {
+        self.0.compress_into(out);
+    }
+
+    /// Decodes the output of [`Self::compress_into`], which is also that of
+    /// the binary serde impls.
+    pub fn decompress(data: &[u8]) -> Result<Self, DecompressError> {
+        let mut ret = Self::default();
+        ret.0.decompress(data)?;
+        Ok(ret)
+    }
+
+    /// Writes the encoding used by the binary serde impls to `writer`,
+    /// without allocating. Dense registers are encoded twice, first to pick
+    /// the encoding.
+    #[cfg(feature = "std")]
+    pub fn write_compressed<W: std::io::Write>(&self, writer: W) -> std::io::Result<()> {
+        let mut writer = io::StdWriter::new(writer);
+        let result = self.encode(&mut writer);
+        writer.finish(result)
+    }
+
+    /// [`Self::write_compressed`], for any [`io::Write`].
+    pub(crate) fn encode<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let registers = self.0.registers();
+        let mut untagged = Measure::default();
+        if registers.iter().all(|&r| r < R::MAX_VALUE) {
+            compress_dense(registers, DenseModel::untagged(R::PRECISION), &mut untagged)?;
+        }
+        let tagged = untagged.first.map_or(true, |first| first == TAGGED);
+        let dense_len = if tagged {
+            let mut measure = Measure::default();
+            compress_dense(registers, DenseModel::tagged(R::MAX_VALUE), &mut measure)?;
+            measure.len + 2
+        } else {
+            untagged.len
+        };
+        if sparse_len(registers, dense_len).is_some() {
+            compress_sparse(registers, writer)
+        } else if tagged {
+            writer.write_all(&[TAGGED, DENSE_TAG])?;
+            compress_dense(registers, DenseModel::tagged(R::MAX_VALUE), writer)
+        } else {
+            compress_dense(registers, DenseModel::untagged(R::PRECISION), writer)
+        }
+    }
+
+    /// Writes the base64 encoding used by the human-readable serde impls to
+    /// `writer`, without allocating.
+    pub fn write_base64<W: core::fmt::Write>(&self, writer: &mut W) -> core::fmt::Result {
+        let mut encoder = io::Base64Writer::new(&base64::prelude::BASE64_STANDARD_NO_PAD, writer);
+        self.encode(&mut encoder).map_err(|_| core::fmt::Error)?;
+        encoder.finish()
+    }
+
+    /// Decodes the output of [`Self::write_base64`] as it goes, without
+    /// allocating.
+    pub fn from_base64(string: &str) -> Result<Self, DecompressError> {
+        let decoder = io::Base64Reader::new(&base64::prelude::BASE64_STANDARD_NO_PAD, string);
+        Self::decode_text(decoder).unwrap_or(Err(DecompressError::InvalidBase64))
+    }
+
+    /// Decodes the output of [`Self::encode`] from a reader that decodes
+    /// text as it goes, returning `None` if the text is invalid.
+    pub(crate) fn decode_text<Rd: Read>(reader: Rd) -> Option<Result<Self, DecompressError>> {
+        let mut reader = Lenient {
+            reader,
+            invalid: false,
+        };
+        let mut ret = Self::default();
+        let result = decompress(
+            ret.0.registers_mut(),
+            R::MAX_VALUE,
+            R::PRECISION,
+            &mut reader,
+        );
+        // The dense decoders stop once every register is decoded, and the
+        // rest must still be valid text.
+        io::drain(&mut reader);
+        (!reader.invalid).then_some(result.map(|_| ret))
+    }
 }
 
 #[cfg(feature = "serde")]
@@ -65,23 +232,17 @@ impl<R: Registers> serde::Serialize for HyperLogLog<R> {
     where
         S: serde::Serializer,
     {
-        let compressed = self.0.compress();
+        struct Base64<'a, R>(&'a HyperLogLog<R>);
+        impl<R: Registers> core::fmt::Display for Base64<'_, R> {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                self.0.write_base64(f)
+            }
+        }
+
         if serializer.is_human_readable() {
-            use base64::prelude::*;
-
-            BUFFER.with(|buffer| {
-                let mut buffer = buffer.borrow_mut();
-                buffer.clear();
-                // SAFETY: An empty `Vec<u8>` is always valid UTF-8.
-                let mut string =
-                    unsafe { String::from_utf8_unchecked(std::mem::take(&mut *buffer)) };
-                BASE64_STANDARD_NO_PAD.encode_string(&compressed, &mut string);
-                let result = serializer.serialize_str(&string);
-                *buffer = string.into_bytes();
-                result
-            })
+            serializer.collect_str(&Base64(self))
         } else {
-            serializer.serialize_bytes(&compressed)
+            serializer.serialize_bytes(&self.0.compress())
         }
     }
 }
@@ -92,11 +253,11 @@ impl<'de, R: Registers> serde::Deserialize<'de> for HyperLogLog<R> {
     where
         D: serde::Deserializer<'de>,
     {
-        struct Visitor<R>(std::marker::PhantomData<R>);
+        struct Visitor<R>(core::marker::PhantomData<R>);
         impl<'de, R: Registers> serde::de::Visitor<'de> for Visitor<R> {
             type Value = HyperLogLog<R>;
 
-            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            fn expecting(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
                 f.write_str("hyperloglog base64 str or bytes")
             }
 
@@ -104,19 +265,7 @@ impl<'de, R: Registers> serde::Deserialize<'de> for HyperLogLog<R> {
             where
                 E: serde::de::Error,
             {
-                use base64::prelude::*;
-                BUFFER.with(|buffer| {
-                    let mut buffer = buffer.borrow_mut();
-                    buffer.clear();
-                    BASE64_STANDARD_NO_PAD
-                        .decode_vec(v, &mut *buffer)
-                        .map_err(|_| serde::de::Error::custom("hyperloglog invaild base64"))?;
-                    let mut ret = HyperLogLog::<R>::default();
-                    ret.0
-                        .decompress(&buffer)
-                        .map_err(|_| serde::de::Error::custom("hyperloglog bytes too short"))?;
-                    Ok(ret)
-                })
+                HyperLogLog::from_base64(v).map_err(serde::de::Error::custom)
             }
 
             fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E>
@@ -124,17 +273,180 @@ impl<'de, R: Registers> serde::Deserialize<'de> for HyperLogLog<R> {
                 E: serde::de::Error,
             {
                 let mut ret = HyperLogLog::<R>::default();
-                ret.0
-                    .decompress(v)
-                    .map_err(|_| serde::de::Error::custom("hyperloglog bytes too short"))?;
+                ret.0.decompress(v).map_err(serde::de::Error::custom)?;
                 Ok(ret)
             }
         }
         if deserializer.is_human_readable() {
-            deserializer.deserialize_str(Visitor::<R>(std::marker::PhantomData))
+            deserializer.deserialize_str(Visitor::<R>(core::marker::PhantomData))
         } else {
-            deserializer.deserialize_bytes(Visitor::<R>(std::marker::PhantomData))
+            deserializer.deserialize_bytes(Visitor::<R>(core::marker::PhantomData))
+        }
+    }
+}
+
+/// A [`HyperLogLog`] borrowed from raw registers, such as those in a
+/// memory-mapped file, without copying them.
+pub struct HyperLogLogRef<'a, R>(&'a R);
+
+impl<R> Clone for HyperLogLogRef<'_, R> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<R> Copy for HyperLogLogRef<'_, R> {}
+
+impl<R> Debug for HyperLogLogRef<'_, R> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("HyperLogLogRef").finish_non_exhaustive()
+    }
+}
+
+impl<'a, R: Registers + bytemuck::AnyBitPattern> HyperLogLogRef<'a, R> {
+    /// Borrows `bytes` as registers, one byte each.
+    pub fn new(bytes: &'a [u8]) -> Result<Self, FromBytesError> {
+        let registers: &R =
+            bytemuck::try_from_bytes(bytes).map_err(|_| FromBytesError::InvalidLength)?;
+        if registers.registers().iter().any(|&r| r > R::MAX_VALUE) {
+            return Err(FromBytesError::InvalidRegisters);
         }
+        Ok(Self(registers))
+    }
+}
+
+impl<R: Registers> HyperLogLogRef<'_, R> {
+    /// Estimate the number of distinct items inserted.
+    pub fn cardinality(&self) -> u64 {
+        let cardinality = self.0.cardinality();
+        debug_assert!(
+            cardinality >= 0.0 && cardinality.is_finite(),
+            "{cardinality}"
+        );
+        libm::round(cardinality) as u64
+    }
+
+    /// What [`Self::cardinality`] is estimated from, for explaining it.
+    pub fn stats(&self) -> Stats {
+        let registers = self.0.registers();
+        let mut histogram = vec![0; R::MAX_VALUE as usize + 1];
+        for &register in registers {
+            histogram[register as usize] += 1;
+        }
+        let zero_registers = histogram[0];
+        let raw_estimate = raw_estimate(registers.iter().copied(), R::PRECISION);
+        let (estimator, bias, estimate) = match (
+            linear_counting(zero_registers, R::PRECISION),
+            bias(raw_estimate, R::PRECISION),
+        ) {
+            (Some(estimate), _) => (Estimator::LinearCounting, 0.0, estimate),
+            (None, Some(bias)) => (Estimator::BiasCorrected, bias, raw_estimate - bias),
+            (None, None) => (Estimator::Raw, 0.0, raw_estimate),
+        };
+        Stats {
+            histogram,
+            zero_registers,
+            estimator,
+            raw_estimate,
+            bias,
+            estimate,
+        }
+    }
+}
+
+/// Which estimate [`HyperLogLog::cardinality`] is, in the order they are
+/// considered.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Estimator {
+    /// Linear counting of the zero registers, while that is at most a
+    /// threshold that depends on the precision.
+    LinearCounting,
+    /// The raw estimate, less its empirical bias, while the raw estimate is
+    /// at most 5 times [`Registers::REGISTERS`].
+    BiasCorrected,
+    /// The raw estimate.
+    Raw,
+}
+
+/// What [`HyperLogLog::cardinality`] is estimated from, as returned by
+/// [`HyperLogLog::stats`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Stats {
+    /// The number of registers of each value, from 0 to
+    /// [`Registers::MAX_VALUE`].
+    pub histogram: Vec<usize>,
+    /// The number of registers that are 0, which is `histogram[0]`.
+    pub zero_registers: usize,
+    pub estimator: Estimator,
+    /// The harmonic mean estimate, before bias correction. Computed whether
+    /// or not it is used.
+    pub raw_estimate: f64,
+    /// The bias subtracted from `raw_estimate`, or 0 unless `estimator` is
+    /// [`Estimator::BiasCorrected`].
+    pub bias: f64,
+    /// The estimate, which [`HyperLogLog::cardinality`] rounds.
+    pub estimate: f64,
+}
+
+impl<R: Registers> From<HyperLogLogRef<'_, R>> for HyperLogLog<R> {
+    fn from(hll: HyperLogLogRef<'_, R>) -> Self {
+        Self(hll.0.clone())
+    }
+}
+
+impl<R: Registers> PartialEq for HyperLogLogRef<'_, R> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<R: Registers> Eq for HyperLogLogRef<'_, R> {}
+
+impl<R: Registers> PartialEq<HyperLogLog<R>> for HyperLogLogRef<'_, R> {
+    fn eq(&self, other: &HyperLogLog<R>) -> bool {
+        *self.0 == other.0
+    }
+}
+
+impl<R: Registers> PartialEq<HyperLogLogRef<'_, R>> for HyperLogLog<R> {
+    fn eq(&self, other: &HyperLogLogRef<'_, R>) -> bool {
+        self.0 == *other.0
+    }
+}
+
+/// An error borrowing registers with [`HyperLogLogRef::new`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FromBytesError {
+    /// The length isn't [`Registers::REGISTERS`].
+    InvalidLength,
+    /// A register value past [`Registers::MAX_VALUE`].
+    InvalidRegisters,
+}
+
+impl core::fmt::Display for FromBytesError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            Self::InvalidLength => "hyperloglog registers invalid length",
+            Self::InvalidRegisters => "hyperloglog registers invalid values",
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for FromBytesError {}
+
+/// Maps items to register updates the same way as another HyperLogLog
+/// implementation, for use with [`HyperLogLog::insert_with`].
+pub trait ItemHasher {
+    /// Returns the index, less than `2^precision`, and the value of the
+    /// register to update for `item`.
+    fn register(&self, item: &[u8], precision: u8) -> (usize, u8);
+
+    /// Whether `item` isn't counted at all, as some implementations do with
+    /// empty items.
+    fn ignores(&self, item: &[u8]) -> bool {
+        let _ = item;
+        false
     }
 }
 
@@ -144,6 +456,8 @@ pub trait Registers: Clone + PartialEq + Eq {
     const PRECISION: u8;
     /// `2^Self::PRECISION`.
     const REGISTERS: usize;
+    /// The largest value [`Self::insert`] stores in a register.
+    const MAX_VALUE: u8 = 64 - Self::PRECISION + 1;
     //const ERROR_RATE: f32;
 
     fn zero() -> Self;
@@ -154,39 +468,17 @@ pub trait Registers: Clone + PartialEq + Eq {
 
     /// Insert a new value into the `HyperLogLog` counter.
     fn insert<V: Hash>(&mut self, value: &V) {
-        let mut sip = SipHasher13::new_with_keys(0x1337_1337, 0x123456789);
-        value.hash(&mut sip);
-        let x = sip.finish();
-
-        // Insert by hash values.
-        let j = x as usize & (Self::REGISTERS - 1);
-        let w = x >> Self::PRECISION;
-        let rho = get_rho(w, 64 - Self::PRECISION);
-        let mjr = &mut self.registers_mut()[j];
-        if rho > *mjr {
-            *mjr = rho;
-        }
+        insert_hash(self, sip_hash(value));
     }
 
     fn cardinality(&self) -> f64 {
         let registers = self.registers();
         let number_of_zero_registers = bytecount::count(registers, 0);
-        if number_of_zero_registers > 0 {
-            let estimate = Self::REGISTERS as f64
-                * (Self::REGISTERS as f64 / number_of_zero_registers as f64).ln();
-            if estimate <= get_threshold(Self::PRECISION) {
-                return estimate;
-            }
-        }
-
-        // ep
-        let sum: f64 = registers.iter().map(|&x| 2.0f64.powi(-(x as i32))).sum();
-        let estimate = get_alpha(Self::PRECISION) * Self::REGISTERS.pow(2) as f64 / sum;
-        if estimate <= (5 * registers.len()) as f64 {
-            estimate - estimate_bias(estimate, Self::PRECISION)
-        } else {
-            estimate
-        }
+        estimate(
+            number_of_zero_registers,
+            registers.iter().copied(),
+            Self::PRECISION,
+        )
     }
 
     /// Merge another `HyperLogLog` counter into the current one.
@@ -202,69 +494,409 @@ pub trait Registers: Clone + PartialEq + Eq {
         self.registers_mut().fill(0);
     }
 
+    /// Encodes the registers, using whichever of the dense and sparse
+    /// encodings is smaller.
     #[cfg(feature = "serde")]
     fn compress(&self) -> Vec<u8> {
-        use arcode::{bitbit::BitWriter, ArithmeticEncoder, EOFKind, Model};
-
-        let data = self.registers();
-
-        let mut model = Model::builder()
-            .num_symbols(compression_symbols(Self::PRECISION))
-            .eof(EOFKind::None)
-            .build();
-        let compressed = std::io::Cursor::new(Vec::new());
-        let mut compressed_writer = BitWriter::new(compressed);
-        let mut encoder = ArithmeticEncoder::new(COMPRESSION_PRECISION);
-
-        for &sym in data {
-            debug_assert!(sym <= 64 - Self::PRECISION);
-            encoder
-                .encode(
-                    sym.min(64 - Self::PRECISION) as u32,
-                    &model,
-                    &mut compressed_writer,
-                )
-                .unwrap();
-            model.update_symbol(sym as u32);
+        let mut compressed = Vec::new();
+        self.compress_into(&mut compressed);
+        compressed
+    }
+
+    /// Like [`Self::compress`], but appends to `out`.
+    #[cfg(feature = "serde")]
+    fn compress_into(&self, out: &mut Vec<u8>) {
+        let registers = self.registers();
+        let start = out.len();
+        if registers.iter().all(|&r| r < Self::MAX_VALUE) {
+            compress_dense(registers, DenseModel::untagged(Self::PRECISION), &mut *out).unwrap();
+        }
+        if out.get(start).map_or(true, |&first| first == TAGGED) {
+            out.truncate(start);
+            out.extend_from_slice(&[TAGGED, DENSE_TAG]);
+            compress_dense(registers, DenseModel::tagged(Self::MAX_VALUE), &mut *out).unwrap();
+        }
+        if sparse_len(registers, out.len() - start).is_some() {
+            out.truncate(start);
+            compress_sparse(registers, out).unwrap();
+        }
+    }
+
+    /// Decodes the output of [`Self::compress`], including that of versions
+    /// which only had the untagged dense encoding.
+    #[cfg(feature = "serde")]
+    fn decompress(&mut self, data: &[u8]) -> Result<(), DecompressError> {
+        decompress(self.registers_mut(), Self::MAX_VALUE, Self::PRECISION, data)
+    }
+}
+
+/// An error decoding the output of [`Registers::compress`].
+#[cfg(feature = "serde")]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DecompressError {
+    /// The input ended before all registers were decoded.
+    Truncated,
+    /// The input starts with an unrecognized encoding tag.
+    UnknownEncoding(u8),
+    /// The input decoded to an out-of-range register index or value.
+    Invalid,
+    /// The input to [`HyperLogLog::from_base64`] isn't valid base64.
+    InvalidBase64,
+    /// The checksum doesn't match, so the input was corrupted.
+    ChecksumMismatch,
+}
+
+#[cfg(feature = "serde")]
+impl core::fmt::Display for DecompressError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Truncated => f.write_str("hyperloglog bytes too short"),
+            Self::UnknownEncoding(tag) => write!(f, "hyperloglog unknown encoding {tag}"),
+            Self::Invalid => f.write_str("hyperloglog bytes invalid"),
+            Self::InvalidBase64 => f.write_str("hyperloglog invalid base64"),
+            Self::ChecksumMismatch => f.write_str("hyperloglog checksum mismatch"),
         }
+    }
+}
+
+#[cfg(feature = "serde")]
+#[cfg(feature = "std")]
+impl std::error::Error for DecompressError {}
+
+/// Prefix of the tagged encodings. Otherwise, the input is in the original,
+/// untagged dense encoding, which only starts with this byte if the first
+/// register holds the largest value its model allows, so `compress` uses the
+/// tagged dense encoding instead in that case.
+///
+/// Every encoding stays decodable: changing how registers are encoded means
+/// adding a tag, not changing what an existing one means.
+#[cfg(feature = "serde")]
+const TAGGED: u8 = 0xff;
+/// Every register, arithmetic coded with an adaptive model that, unlike the
+/// untagged one, includes [`Registers::MAX_VALUE`].
+#[cfg(feature = "serde")]
+const DENSE_TAG: u8 = 0;
+/// Only the non-zero registers, as varint `(index delta << 6) | value` pairs.
+#[cfg(feature = "serde")]
+const SPARSE_TAG: u8 = 1;
+/// Another encoding with a checksum, laid out as described in [`checked`].
+#[cfg(feature = "serde")]
+const CHECKED_TAG: u8 = 2;
 
-        // encoder.encode(model.eof(), &model, &mut compressed_writer).unwrap();
-        encoder.finish_encode(&mut compressed_writer).unwrap();
-        compressed_writer.pad_to_byte().unwrap();
+/// An encoding of the output of [`Registers::compress`].
+#[cfg(feature = "serde")]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum CompressedFormat {
+    /// Every register, arithmetic coded, without a tag. The only encoding
+    /// before tags were added, and still used when it is the smallest.
+    UntaggedDense,
+    /// Every register, arithmetic coded, including [`Registers::MAX_VALUE`].
+    Dense,
+    /// Only the non-zero registers.
+    Sparse,
+    /// Another encoding followed by a checksum, as written by
+    /// [`HyperLogLog::compress_checked_into`].
+    Checked,
+}
 
-        compressed_writer.get_ref().get_ref().clone()
+#[cfg(feature = "serde")]
+impl CompressedFormat {
+    /// The encoding of `data`, without decoding it.
+    pub fn of(data: &[u8]) -> Result<Self, DecompressError> {
+        Self::from_prefix(data.get(..2).unwrap_or(data))
     }
 
-    #[cfg(feature = "serde")]
-    fn decompress(&mut self, data: &[u8]) -> Result<(), ()> {
-        use arcode::{
-            bitbit::{BitReader, MSB},
-            ArithmeticDecoder, EOFKind, Model,
-        };
+    fn from_prefix(prefix: &[u8]) -> Result<Self, DecompressError> {
+        match *prefix {
+            [TAGGED, DENSE_TAG] => Ok(Self::Dense),
+            [TAGGED, SPARSE_TAG] => Ok(Self::Sparse),
+            [TAGGED, CHECKED_TAG] => Ok(Self::Checked),
+            [TAGGED, tag] => Err(DecompressError::UnknownEncoding(tag)),
+            [TAGGED] => Err(DecompressError::Truncated),
+            _ => Ok(Self::UntaggedDense),
+        }
+    }
+}
 
-        let mut model = Model::builder()
-            .num_symbols(compression_symbols(Self::PRECISION))
-            .eof(EOFKind::None)
-            .build();
+/// The adaptive model of a dense encoding. Its parameters are part of the
+/// encoding, so they must never change.
+#[cfg(feature = "serde")]
+#[derive(Copy, Clone)]
+struct DenseModel {
+    symbols: u32,
+    precision: u64,
+}
 
-        let mut input_reader = BitReader::<_, MSB>::new(data);
-        let mut decoder = ArithmeticDecoder::new(COMPRESSION_PRECISION);
+#[cfg(feature = "serde")]
+impl DenseModel {
+    /// [`CompressedFormat::UntaggedDense`], which can't encode
+    /// [`Registers::MAX_VALUE`].
+    fn untagged(precision: u8) -> Self {
+        Self {
+            symbols: 64 + 1 - precision as u32,
+            precision: 48,
+        }
+    }
 
-        for decompressed in self.registers_mut() {
-            let sym = decoder.decode(&model, &mut input_reader).map_err(|_| ())?;
-            model.update_symbol(sym);
-            *decompressed = sym as u8;
+    /// [`CompressedFormat::Dense`].
+    fn tagged(max_value: u8) -> Self {
+        Self {
+            symbols: max_value as u32 + 1,
+            precision: 48,
         }
+    }
+
+    fn build(self) -> arithmetic::Model {
+        arithmetic::Model::new(self.symbols)
+    }
+}
+
+/// Writes the arithmetic coded registers, each less than the model's
+/// symbols, to `writer`.
+#[cfg(feature = "serde")]
+fn compress_dense<W: Write>(registers: &[u8], model: DenseModel, writer: W) -> io::Result<()> {
+    let symbols = model.symbols;
+    let mut encoder = arithmetic::Encoder::new(model.precision, writer);
+    let mut model = model.build();
+
+    for &sym in registers {
+        debug_assert!((sym as u32) < symbols);
+        encoder.encode(sym as u32, &model)?;
+        model.update(sym as u32);
+    }
+
+    encoder.finish()
+}
+
+/// Decodes any of the encodings from `reader`, which is read one byte at a
+/// time, treating read errors like the end of the input.
+#[cfg(feature = "serde")]
+fn decompress<Rd: Read>(
+    registers: &mut [u8],
+    max_value: u8,
+    precision: u8,
+    mut reader: Rd,
+) -> Result<(), DecompressError> {
+    let (prefix, len) = read_prefix(&mut reader);
+    match CompressedFormat::from_prefix(&prefix[..len])? {
+        CompressedFormat::Checked => checked::decompress(registers, max_value, precision, reader),
+        format => decompress_format(
+            format,
+            &prefix[..len],
+            registers,
+            max_value,
+            precision,
+            reader,
+        ),
+    }
+}
+
+/// Like [`decompress`], but rejecting [`CompressedFormat::Checked`], which
+/// can't be nested.
+#[cfg(feature = "serde")]
+fn decompress_unchecked<Rd: Read>(
+    registers: &mut [u8],
+    max_value: u8,
+    precision: u8,
+    mut reader: Rd,
+) -> Result<(), DecompressError> {
+    let (prefix, len) = read_prefix(&mut reader);
+    match CompressedFormat::from_prefix(&prefix[..len])? {
+        CompressedFormat::Checked => Err(DecompressError::Invalid),
+        format => decompress_format(
+            format,
+            &prefix[..len],
+            registers,
+            max_value,
+            precision,
+            reader,
+        ),
+    }
+}
+
+#[cfg(feature = "serde")]
+fn read_prefix<Rd: Read>(reader: &mut Rd) -> ([u8; 2], usize) {
+    let mut prefix = [0; 2];
+    let mut len = 0;
+    while len < prefix.len() {
+        match reader.read(&mut prefix[len..]) {
+            Ok(0) | Err(_) => break,
+            Ok(read) => len += read,
+        }
+    }
+    (prefix, len)
+}
+
+/// Decodes the rest of an encoding, given the prefix it was identified by.
+#[cfg(feature = "serde")]
+fn decompress_format<Rd: Read>(
+    format: CompressedFormat,
+    prefix: &[u8],
+    registers: &mut [u8],
+    max_value: u8,
+    precision: u8,
+    reader: Rd,
+) -> Result<(), DecompressError> {
+    match format {
+        CompressedFormat::UntaggedDense => decompress_dense(
+            registers,
+            DenseModel::untagged(precision),
+            prefix.chain(reader),
+        ),
+        CompressedFormat::Dense => {
+            decompress_dense(registers, DenseModel::tagged(max_value), reader)
+        }
+        CompressedFormat::Sparse => decompress_sparse(registers, max_value, reader),
+        CompressedFormat::Checked => unreachable!(),
+    }
+}
+
+#[cfg(feature = "serde")]
+fn decompress_dense<Rd: Read>(
+    registers: &mut [u8],
+    model: DenseModel,
+    reader: Rd,
+) -> Result<(), DecompressError> {
+    let mut decoder = arithmetic::Decoder::new(model.precision, reader);
+    let mut model = model.build();
 
+    for decompressed in registers {
+        let sym = decoder.decode(&model)?;
+        model.update(sym);
+        *decompressed = sym as u8;
+    }
+
+    Ok(())
+}
+
+/// Varint `(index delta << 6) | value` pairs for the non-zero registers.
+fn sparse_pairs(registers: &[u8]) -> impl Iterator<Item = u64> + '_ {
+    let mut next = 0;
+    registers
+        .iter()
+        .enumerate()
+        .filter(|(_, &value)| value != 0)
+        .map(move |(i, &value)| {
+            debug_assert!(value < 64);
+            let pair = (((i - next) as u64) << 6) | value as u64;
+            next = i + 1;
+            pair
+        })
+}
+
+fn varint_len(pair: u64) -> usize {
+    (64 - pair.leading_zeros() as usize).max(1).div_ceil(7)
+}
+
+/// The length of the sparse encoding, or `None` if it wouldn't be shorter
+/// than `limit`.
+#[cfg(feature = "serde")]
+fn sparse_len(registers: &[u8], limit: usize) -> Option<usize> {
+    let mut len = 2;
+    for pair in sparse_pairs(registers) {
+        len += varint_len(pair);
+        if len >= limit {
+            return None;
+        }
+    }
+    (len < limit).then_some(len)
+}
+
+#[cfg(feature = "serde")]
+fn compress_sparse<W: Write>(registers: &[u8], mut writer: W) -> io::Result<()> {
+    writer.write_all(&[TAGGED, SPARSE_TAG])?;
+    for mut pair in sparse_pairs(registers) {
+        let mut varint = [0; 10];
+        let mut len = 0;
+        while pair >= 0x80 {
+            varint[len] = pair as u8 | 0x80;
+            pair >>= 7;
+            len += 1;
+        }
+        varint[len] = pair as u8;
+        writer.write_all(&varint[..=len])?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "serde")]
+fn decompress_sparse<Rd: Read>(
+    registers: &mut [u8],
+    max_value: u8,
+    mut reader: Rd,
+) -> Result<(), DecompressError> {
+    let mut read_byte = || reader.read_byte();
+    registers.fill(0);
+    let mut next = 0usize;
+    while let Some(mut byte) = read_byte() {
+        let mut pair = 0u64;
+        let mut shift = 0;
+        loop {
+            if shift >= 64 {
+                return Err(DecompressError::Invalid);
+            }
+            pair |= ((byte & 0x7f) as u64) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                break;
+            }
+            byte = read_byte().ok_or(DecompressError::Truncated)?;
+        }
+        let value = (pair & 0x3f) as u8;
+        let index = usize::try_from(pair >> 6)
+            .ok()
+            .and_then(|delta| next.checked_add(delta))
+            .filter(|&index| index < registers.len())
+            .ok_or(DecompressError::Invalid)?;
+        if value == 0 || value > max_value {
+            return Err(DecompressError::Invalid);
+        }
+        registers[index] = value;
+        next = index + 1;
+    }
+    Ok(())
+}
+
+/// Counts the bytes written to it, to measure an encoding without storing
+/// it.
+#[cfg(feature = "serde")]
+#[derive(Default)]
+struct Measure {
+    len: usize,
+    first: Option<u8>,
+}
+
+#[cfg(feature = "serde")]
+impl Write for Measure {
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        if self.len == 0 {
+            self.first = buf.first().copied();
+        }
+        self.len += buf.len();
         Ok(())
     }
 }
 
+/// Reads decoded text, ending early rather than failing on invalid input
+/// and remembering that it did, since the dense decoders can't tell read
+/// errors apart from the end of the input.
 #[cfg(feature = "serde")]
-const COMPRESSION_PRECISION: u64 = 48;
+struct Lenient<Rd> {
+    reader: Rd,
+    invalid: bool,
+}
+
 #[cfg(feature = "serde")]
-fn compression_symbols(precision: u8) -> u32 {
-    64 + 1 - precision as u32
+impl<Rd: Read> Read for Lenient<Rd> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.invalid {
+            return Ok(0);
+        }
+        Ok(self.reader.read(buf).unwrap_or_else(|_| {
+            self.invalid = true;
+            0
+        }))
+    }
 }
 
 macro_rules! impl_u8_array {
@@ -298,11 +930,82 @@ impl_u8_array!(8, 256);
 impl_u8_array!(9, 512);
 impl_u8_array!(10, 1024);
 impl_u8_array!(11, 2048);
+impl_u8_array!(12, 4096);
+impl_u8_array!(13, 8192);
+impl_u8_array!(14, 16384);
+impl_u8_array!(15, 32768);
+impl_u8_array!(16, 65536);
+impl_u8_array!(17, 131072);
+impl_u8_array!(18, 262144);
+
+/// The hash [`Registers::insert`] counts an item by.
+fn sip_hash<V: Hash + ?Sized>(value: &V) -> u64 {
+    let mut sip = SipHasher13::new_with_keys(0x1337_1337, 0x123456789);
+    value.hash(&mut sip);
+    sip.finish()
+}
+
+/// The register a hash is counted in, and the value it counts.
+fn register_of(x: u64, p: u8) -> (usize, u8) {
+    let j = x as usize & ((1 << p) - 1);
+    let w = x >> p;
+    (j, get_rho(w, 64 - p))
+}
+
+fn insert_hash<R: Registers>(registers: &mut R, x: u64) {
+    // Insert by hash values.
+    let (j, rho) = register_of(x, R::PRECISION);
+    let mjr = &mut registers.registers_mut()[j];
+    if rho > *mjr {
+        *mjr = rho;
+    }
+}
 
 fn get_threshold(p: u8) -> f64 {
     THRESHOLD_DATA[p as usize - 4]
 }
 
+/// The linear counting estimate, if there are zero registers and it is at
+/// most the threshold below which it is more accurate.
+fn linear_counting(number_of_zero_registers: usize, p: u8) -> Option<f64> {
+    if number_of_zero_registers == 0 {
+        return None;
+    }
+    let registers = (1usize << p) as f64;
+    let estimate = registers * libm::log(registers / number_of_zero_registers as f64);
+    (estimate <= get_threshold(p)).then_some(estimate)
+}
+
+/// The estimate of `2^p` registers, `number_of_zero_registers` of which are
+/// zero.
+fn estimate(
+    number_of_zero_registers: usize,
+    registers: impl IntoIterator<Item = u8>,
+    p: u8,
+) -> f64 {
+    if let Some(estimate) = linear_counting(number_of_zero_registers, p) {
+        return estimate;
+    }
+    let estimate = raw_estimate(registers, p);
+    estimate - bias(estimate, p).unwrap_or(0.0)
+}
+
+/// The harmonic mean estimate of `2^p` registers, before bias correction.
+fn raw_estimate(registers: impl IntoIterator<Item = u8>, p: u8) -> f64 {
+    // ep
+    let sum: f64 = registers
+        .into_iter()
+        .map(|x| libm::scalbn(1.0, -(x as i32)))
+        .sum();
+    let registers = (1usize << p) as f64;
+    get_alpha(p) * registers * registers / sum
+}
+
+/// The bias of a raw estimate, if it is low enough to be corrected.
+fn bias(estimate: f64, p: u8) -> Option<f64> {
+    (estimate <= (5usize << p) as f64).then(|| estimate_bias(estimate, p))
+}
+
 fn get_alpha(p: u8) -> f64 {
     assert!(p >= 4);
     assert!(p <= 18);
@@ -324,6 +1027,26 @@ fn get_rho(w: u64, max_width: u8) -> u8 {
     rho
 }
 
+/// Maps a register at precision `from` to precision `to`, for hashes whose
+/// index is taken from the high bits. The index bits that are dropped
+/// precede the bits the value was counted from.
+#[cfg(any(
+    feature = "airlift",
+    feature = "spark",
+    feature = "streamlib",
+    feature = "zetasketch"
+))]
+fn fold(index: usize, value: u8, from: u8, to: u8) -> (usize, u8) {
+    let shift = from - to;
+    let dropped = index & ((1 << shift) - 1);
+    let value = if dropped == 0 {
+        shift + value
+    } else {
+        get_rho(dropped as u64, shift)
+    };
+    (index >> shift, value)
+}
+
 fn estimate_bias(estimate: f64, p: u8) -> f64 {
     let bias_vector = BIAS_DATA[(p - 4) as usize];
     let estimate_vector = RAW_ESTIMATE_DATA[(p - 4) as usize];
@@ -331,11 +1054,7 @@ fn estimate_bias(estimate: f64, p: u8) -> f64 {
     // Since the estimates are sorted, we can use a partition point to find the nearest neighbors
     let partition_point = estimate_vector.partition_point(|&x| x < estimate);
 
-    let mut min = if partition_point > 6 {
-        partition_point - 6
-    } else {
-        0
-    };
+    let mut min = partition_point.saturating_sub(6);
     let mut max = core::cmp::min(partition_point + 6, estimate_vector.len());
 
     while max - min != 6 {
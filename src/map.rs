@@ -0,0 +1,275 @@
+//! [`HyperLogLog`]s by key, for counting the distinct items of each of many
+//! groups, such as the users of each country or page.
+//!
+//! Most groups of a skewed key see few items, so each key's registers start
+//! sparse, as the non-zero ones only, and become dense once that would take
+//! less memory.
+//!
+//! Keys must be [`Ord`] rather than [`Hash`], since they are kept in a
+//! [`BTreeMap`], which is available without `std`. In return, the map is
+//! deterministic: [`HyperLogLogMap::keys`] are in increasing order, and
+//! [`HyperLogLogMap::top_k`] breaks ties by that order.
+
+use crate::{register_of, sip_hash, HyperLogLog, Registers};
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::cmp::Reverse;
+use core::fmt;
+use core::hash::Hash;
+
+/// A [`HyperLogLog`] for each key.
+#[derive(Clone, PartialEq, Eq)]
+pub struct HyperLogLogMap<K, R> {
+    sketches: BTreeMap<K, Sketch<R>>,
+}
+
+/// The registers of a key. Whether they are sparse depends only on them, so
+/// equal registers are equal sketches.
+#[derive(Clone, PartialEq, Eq)]
+enum Sketch<R> {
+    /// The non-zero registers, as `index << 8 | value`, in increasing order
+    /// of index, while there are at most [`Sketch::max_sparse`].
+    Sparse(Vec<u32>),
+    Dense(Box<HyperLogLog<R>>),
+}
+
+impl<R: Registers> Sketch<R> {
+    /// The most non-zero registers kept sparse, which take as much memory as
+    /// dense ones.
+    fn max_sparse() -> usize {
+        R::REGISTERS / 4
+    }
+
+    /// Sparse or dense, whichever `hll`'s registers should be.
+    #[cfg(feature = "serde")]
+    fn new(hll: HyperLogLog<R>) -> Self {
+        let registers = hll.0.registers();
+        if registers.iter().filter(|&&r| r != 0).count() > Self::max_sparse() {
+            return Self::Dense(Box::new(hll));
+        }
+        Self::Sparse(
+            registers
+                .iter()
+                .enumerate()
+                .filter(|&(_, &r)| r != 0)
+                .map(|(index, &r)| (index as u32) << 8 | r as u32)
+                .collect(),
+        )
+    }
+
+    fn insert(&mut self, index: usize, value: u8) {
+        match self {
+            Self::Sparse(sparse) => {
+                match sparse.binary_search_by_key(&(index as u32), |&r| r >> 8) {
+                    Ok(i) => sparse[i] = sparse[i].max((index as u32) << 8 | value as u32),
+                    Err(i) => {
+                        sparse.insert(i, (index as u32) << 8 | value as u32);
+                        if sparse.len() > Self::max_sparse() {
+                            *self = Self::Dense(Box::new(self.to_hyperloglog()));
+                        }
+                    }
+                }
+            }
+            Self::Dense(hll) => {
+                let register = &mut hll.0.registers_mut()[index];
+                *register = (*register).max(value);
+            }
+        }
+    }
+
+    fn merge(&mut self, other: &Self) {
+        match (&mut *self, other) {
+            (Self::Dense(hll), Self::Dense(other)) => hll.merge(other),
+            (_, Self::Sparse(sparse)) => {
+                for &r in sparse {
+                    self.insert((r >> 8) as usize, r as u8);
+                }
+            }
+            (Self::Sparse(_), Self::Dense(other)) => {
+                let mut hll = (**other).clone();
+                hll.merge(&self.to_hyperloglog());
+                *self = Self::Dense(Box::new(hll));
+            }
+        }
+    }
+
+    fn to_hyperloglog(&self) -> HyperLogLog<R> {
+        match self {
+            Self::Sparse(sparse) => {
+                let mut ret = HyperLogLog::<R>::default();
+                let registers = ret.0.registers_mut();
+                for &r in sparse {
+                    registers[(r >> 8) as usize] = r as u8;
+                }
+                ret
+            }
+            Self::Dense(hll) => (**hll).clone(),
+        }
+    }
+}
+
+impl<K, R> fmt::Debug for HyperLogLogMap<K, R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HyperLogLogMap").finish_non_exhaustive()
+    }
+}
+
+impl<K, R> Default for HyperLogLogMap<K, R> {
+    fn default() -> Self {
+        Self {
+            sketches: BTreeMap::new(),
+        }
+    }
+}
+
+impl<K: Ord, R: Registers> HyperLogLogMap<K, R> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count an item for `key` if it is distinct among that key's.
+    pub fn insert<V: Hash>(&mut self, key: K, item: &V) {
+        self.insert_hash(key, sip_hash(item));
+    }
+
+    /// Like [`HyperLogLog::insert_hash`], for `key`.
+    pub fn insert_hash(&mut self, key: K, hash: u64) {
+        let (index, value) = register_of(hash, R::PRECISION);
+        self.sketches
+            .entry(key)
+            .or_insert_with(|| Sketch::Sparse(Vec::new()))
+            .insert(index, value);
+    }
+
+    /// The counter of `key`'s items, if any were inserted.
+    pub fn get<Q>(&self, key: &Q) -> Option<HyperLogLog<R>>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.sketches.get(key).map(Sketch::to_hyperloglog)
+    }
+
+    /// Estimate the number of distinct items inserted for `key`.
+    pub fn cardinality<Q>(&self, key: &Q) -> u64
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.get(key).map_or(0, |hll| hll.cardinality())
+    }
+
+    /// The counter of the items of every key.
+    pub fn total(&self) -> HyperLogLog<R> {
+        let mut ret = HyperLogLog::<R>::default();
+        for sketch in self.sketches.values() {
+            match sketch {
+                Sketch::Sparse(sparse) => {
+                    let registers = ret.0.registers_mut();
+                    for &r in sparse {
+                        let register = &mut registers[(r >> 8) as usize];
+                        *register = (*register).max(r as u8);
+                    }
+                }
+                Sketch::Dense(hll) => ret.merge(hll),
+            }
+        }
+        ret
+    }
+
+    /// Estimate the number of distinct items inserted for any key.
+    pub fn total_cardinality(&self) -> u64 {
+        self.total().cardinality()
+    }
+
+    /// The `k` keys with the largest cardinalities, largest first, and
+    /// those of equal cardinality in order.
+    pub fn top_k(&self, k: usize) -> Vec<(&K, u64)> {
+        let mut ret: Vec<_> = self
+            .sketches
+            .iter()
+            .map(|(key, sketch)| (key, sketch.to_hyperloglog().cardinality()))
+            .collect();
+        ret.sort_by_key(|&(_, cardinality)| Reverse(cardinality));
+        ret.truncate(k);
+        ret
+    }
+
+    /// Merge the items of every key of another map.
+    pub fn merge(&mut self, other: &Self)
+    where
+        K: Clone,
+    {
+        for (key, sketch) in &other.sketches {
+            match self.sketches.get_mut(key) {
+                Some(existing) => existing.merge(sketch),
+                None => {
+                    self.sketches.insert(key.clone(), sketch.clone());
+                }
+            }
+        }
+    }
+
+    /// Forgets `key`'s items, returning their counter.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<HyperLogLog<R>>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.sketches
+            .remove(key)
+            .map(|sketch| sketch.to_hyperloglog())
+    }
+
+    /// The keys with items, in order.
+    pub fn keys(&self) -> impl ExactSizeIterator<Item = &K> + '_ {
+        self.sketches.keys()
+    }
+
+    /// The number of keys with items.
+    pub fn len(&self) -> usize {
+        self.sketches.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sketches.is_empty()
+    }
+}
+
+/// Serializes as a map of each key to its [`HyperLogLog`], so it is
+/// compatible with a `HashMap<K, HyperLogLog<R>>` or `BTreeMap`.
+#[cfg(feature = "serde")]
+impl<K: serde::Serialize, R: Registers> serde::Serialize for HyperLogLogMap<K, R> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_map(
+            self.sketches
+                .iter()
+                .map(|(key, sketch)| (key, sketch.to_hyperloglog())),
+        )
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, K, R> serde::Deserialize<'de> for HyperLogLogMap<K, R>
+where
+    K: Ord + serde::Deserialize<'de>,
+    R: Registers,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let map = <BTreeMap<K, HyperLogLog<R>> as serde::Deserialize>::deserialize(deserializer)?;
+        Ok(Self {
+            sketches: map
+                .into_iter()
+                .map(|(key, hll)| (key, Sketch::new(hll)))
+                .collect(),
+        })
+    }
+}
@@ -0,0 +1,66 @@
+//! MurmurHash3, shared by the formats that hash items with it.
+
+/// Austin Appleby's 128-bit x64 MurmurHash3, as its two 64-bit halves.
+pub fn murmurhash3_x64_128(key: &[u8], seed: u64) -> (u64, u64) {
+    const C1: u64 = 0x87c37b91114253d5;
+    const C2: u64 = 0x4cf5ad432745937f;
+
+    let mut h1 = seed;
+    let mut h2 = seed;
+
+    let mut chunks = key.chunks_exact(16);
+    for chunk in &mut chunks {
+        let k1 = u64::from_le_bytes(chunk[..8].try_into().unwrap());
+        let k2 = u64::from_le_bytes(chunk[8..].try_into().unwrap());
+
+        h1 ^= k1.wrapping_mul(C1).rotate_left(31).wrapping_mul(C2);
+        h1 = h1
+            .rotate_left(27)
+            .wrapping_add(h2)
+            .wrapping_mul(5)
+            .wrapping_add(0x52dce729);
+
+        h2 ^= k2.wrapping_mul(C2).rotate_left(33).wrapping_mul(C1);
+        h2 = h2
+            .rotate_left(31)
+            .wrapping_add(h1)
+            .wrapping_mul(5)
+            .wrapping_add(0x38495ab5);
+    }
+
+    let tail = chunks.remainder();
+    let mut k1 = 0u64;
+    let mut k2 = 0u64;
+    for (i, &byte) in tail.iter().enumerate() {
+        if i < 8 {
+            k1 |= (byte as u64) << (i * 8);
+        } else {
+            k2 |= (byte as u64) << ((i - 8) * 8);
+        }
+    }
+    if tail.len() > 8 {
+        h2 ^= k2.wrapping_mul(C2).rotate_left(33).wrapping_mul(C1);
+    }
+    if !tail.is_empty() {
+        h1 ^= k1.wrapping_mul(C1).rotate_left(31).wrapping_mul(C2);
+    }
+
+    h1 ^= key.len() as u64;
+    h2 ^= key.len() as u64;
+    h1 = h1.wrapping_add(h2);
+    h2 = h2.wrapping_add(h1);
+    h1 = fmix64(h1);
+    h2 = fmix64(h2);
+    h1 = h1.wrapping_add(h2);
+    h2 = h2.wrapping_add(h1);
+    (h1, h2)
+}
+
+fn fmix64(mut k: u64) -> u64 {
+    k ^= k >> 33;
+    k = k.wrapping_mul(0xff51afd7ed558ccd);
+    k ^= k >> 33;
+    k = k.wrapping_mul(0xc4ceb9fe1a85ec53);
+    k ^= k >> 33;
+    k
+}
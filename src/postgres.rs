@@ -0,0 +1,202 @@
+//! Conversion to and from the storage format of the `hll` PostgreSQL
+//! extension from Aggregate Knowledge.
+//!
+//! Items must be inserted with [`MurmurHash3`] to stay compatible with the
+//! `hll_hash_*` functions. `log2m` must equal [`Registers::PRECISION`],
+//! except in `EXPLICIT` sketches, which store hashes rather than registers.
+
+pub use crate::murmur3::murmurhash3_x64_128;
+use crate::{HyperLogLog, ItemHasher, Registers};
+use alloc::{vec, vec::Vec};
+use core::fmt;
+
+/// An error reading a stored `hll`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PostgresError {
+    /// Missing header, or an unknown version or type.
+    InvalidHeader,
+    /// `log2m` differs from [`Registers::PRECISION`].
+    PrecisionMismatch(u8),
+    /// Wrong length for the type and parameters.
+    InvalidLength,
+    /// A register value past [`Registers::MAX_VALUE`].
+    InvalidRegisters,
+}
+
+impl fmt::Display for PostgresError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidHeader => f.write_str("postgres hll invalid header"),
+            Self::PrecisionMismatch(log2m) => {
+                write!(f, "postgres hll log2m {log2m} differs from precision")
+            }
+            Self::InvalidLength => f.write_str("postgres hll invalid length"),
+            Self::InvalidRegisters => f.write_str("postgres hll invalid registers"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PostgresError {}
+
+const VERSION: u8 = 1;
+const EMPTY: u8 = 1;
+const EXPLICIT: u8 = 2;
+const SPARSE: u8 = 3;
+const FULL: u8 = 4;
+/// Sparse enabled, with an automatic explicit cutoff, like `hll_empty()`.
+const CUTOFF: u8 = 0x40 | 63;
+
+impl<R: Registers> HyperLogLog<R> {
+    /// Reads a stored `hll` of any type.
+    pub fn from_postgres(bytes: &[u8]) -> Result<Self, PostgresError> {
+        let &[version_type, parameters, _cutoff, ref data @ ..] = bytes else {
+            return Err(PostgresError::InvalidHeader);
+        };
+        if version_type >> 4 != VERSION {
+            return Err(PostgresError::InvalidHeader);
+        }
+        let regwidth = (parameters >> 5) + 1;
+        let log2m = parameters & 0x1f;
+
+        let mut ret = Self::default();
+        match version_type & 0xf {
+            EMPTY => {
+                if !data.is_empty() {
+                    return Err(PostgresError::InvalidLength);
+                }
+            }
+            EXPLICIT => {
+                if data.len() % 8 != 0 {
+                    return Err(PostgresError::InvalidLength);
+                }
+                for hash in data.chunks_exact(8) {
+                    let (index, value) =
+                        register(u64::from_be_bytes(hash.try_into().unwrap()), R::PRECISION);
+                    let register = &mut ret.0.registers_mut()[index];
+                    *register = (*register).max(value);
+                }
+            }
+            SPARSE => {
+                if log2m != R::PRECISION {
+                    return Err(PostgresError::PrecisionMismatch(log2m));
+                }
+                let chunk_bits = (regwidth + log2m) as usize;
+                let registers = ret.0.registers_mut();
+                for i in 0..data.len() * 8 / chunk_bits {
+                    let chunk = read_bits(data, i * chunk_bits, chunk_bits);
+                    let index = (chunk >> regwidth) as usize;
+                    let value = (chunk & ((1 << regwidth) - 1)) as u8;
+                    if value > R::MAX_VALUE {
+                        return Err(PostgresError::InvalidRegisters);
+                    }
+                    // Zero values can only be padding.
+                    registers[index] = registers[index].max(value);
+                }
+            }
+            FULL => {
+                if log2m != R::PRECISION {
+                    return Err(PostgresError::PrecisionMismatch(log2m));
+                }
+                if data.len() != (R::REGISTERS * regwidth as usize).div_ceil(8) {
+                    return Err(PostgresError::InvalidLength);
+                }
+                for (i, register) in ret.0.registers_mut().iter_mut().enumerate() {
+                    *register = read_bits(data, i * regwidth as usize, regwidth as usize) as u8;
+                    if *register > R::MAX_VALUE {
+                        return Err(PostgresError::InvalidRegisters);
+                    }
+                }
+            }
+            _ => return Err(PostgresError::InvalidHeader),
+        }
+        Ok(ret)
+    }
+
+    /// Writes a stored `hll` with `log2m` equal to [`Registers::PRECISION`]
+    /// and the given `regwidth` (`1..=8`, 5 by default in PostgreSQL).
+    ///
+    /// `EXPLICIT` is never written, since the hashes aren't kept. Otherwise,
+    /// the type is `EMPTY` if no item was inserted, or whichever of `SPARSE`
+    /// and `FULL` takes fewer bytes, which the extension reads regardless of
+    /// its own cutoffs.
+    ///
+    /// Register values that don't fit in `regwidth` bits are saturated.
+    pub fn to_postgres(&self, regwidth: u8) -> Vec<u8> {
+        assert!((1..=8).contains(&regwidth), "{regwidth}");
+        let registers = self.0.registers();
+        let log2m = R::PRECISION;
+        let max_value = ((1u16 << regwidth) - 1) as u8;
+        let mut ret = vec![0, (regwidth - 1) << 5 | log2m, CUTOFF];
+
+        let non_zero = registers.len() - bytecount::count(registers, 0);
+        let chunk_bits = (regwidth + log2m) as usize;
+        let sparse_len = (non_zero * chunk_bits).div_ceil(8);
+        let full_len = (registers.len() * regwidth as usize).div_ceil(8);
+        if non_zero == 0 {
+            ret[0] = VERSION << 4 | EMPTY;
+        } else if sparse_len < full_len {
+            ret[0] = VERSION << 4 | SPARSE;
+            ret.resize(3 + sparse_len, 0);
+            let chunks = registers
+                .iter()
+                .enumerate()
+                .filter(|(_, &value)| value != 0);
+            for (i, (index, &value)) in chunks.enumerate() {
+                let chunk = (index as u64) << regwidth | value.min(max_value) as u64;
+                write_bits(&mut ret[3..], i * chunk_bits, chunk_bits, chunk);
+            }
+        } else {
+            ret[0] = VERSION << 4 | FULL;
+            ret.resize(3 + full_len, 0);
+            for (i, &value) in registers.iter().enumerate() {
+                let bits = regwidth as usize;
+                write_bits(&mut ret[3..], i * bits, bits, value.min(max_value) as u64);
+            }
+        }
+        ret
+    }
+}
+
+/// Reads `len` bits starting at bit `start`, most significant bit first.
+fn read_bits(data: &[u8], start: usize, len: usize) -> u64 {
+    (start..start + len).fold(0, |bits, i| {
+        bits << 1 | (data[i / 8] >> (7 - i % 8) & 1) as u64
+    })
+}
+
+fn write_bits(data: &mut [u8], start: usize, len: usize, bits: u64) {
+    for (n, i) in (start..start + len).enumerate() {
+        data[i / 8] |= ((bits >> (len - 1 - n) & 1) as u8) << (7 - i % 8);
+    }
+}
+
+/// Maps a 64-bit hash to a register like `hll_add`: index from the low bits,
+/// and value from the trailing zeros of the rest, or zero if they are all
+/// zero.
+fn register(hash: u64, precision: u8) -> (usize, u8) {
+    let index = hash as usize & ((1 << precision) - 1);
+    let rest = hash >> precision;
+    let value = if rest == 0 {
+        0
+    } else {
+        rest.trailing_zeros() as u8 + 1
+    };
+    (index, value)
+}
+
+/// Hashes items like the `hll_hash_*` functions: the first half of
+/// [`murmurhash3_x64_128`] with a seed of 0, which is then added like
+/// `hll_add`.
+///
+/// `hll_hash_integer` and `hll_hash_bigint` hash their argument's
+/// little-endian bytes, and `hll_hash_text` and `hll_hash_bytea` hash the
+/// bytes as is.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct MurmurHash3;
+
+impl ItemHasher for MurmurHash3 {
+    fn register(&self, item: &[u8], precision: u8) -> (usize, u8) {
+        register(murmurhash3_x64_128(item, 0).0, precision)
+    }
+}
@@ -0,0 +1,149 @@
+//! A Protocol Buffers message for a sketch, for storing it alongside what's
+//! needed to tell whether it can be merged with another.
+//!
+//! The message is defined in `proto/hyperloglog.proto`, for other languages:
+//!
+//! ```proto
+//! message Sketch {
+//!   uint32 precision = 1;
+//!   HashId hash = 2;
+//!   Encoding encoding = 3;
+//!   bytes payload = 4;
+//! }
+//! ```
+
+use crate::{DecompressError, HyperLogLog, Registers};
+use alloc::vec::Vec;
+use core::fmt;
+
+/// A serialized [`HyperLogLog`].
+#[derive(Clone, PartialEq, Eq, Hash, prost::Message)]
+pub struct Sketch {
+    /// [`Registers::PRECISION`].
+    #[prost(uint32, tag = "1")]
+    pub precision: u32,
+    /// How items were hashed, which must match to merge sketches.
+    #[prost(enumeration = "HashId", tag = "2")]
+    pub hash: i32,
+    /// How the registers are encoded in the payload.
+    #[prost(enumeration = "Encoding", tag = "3")]
+    pub encoding: i32,
+    #[prost(bytes = "vec", tag = "4")]
+    pub payload: Vec<u8>,
+}
+
+/// How items were hashed. Other values may be used for other hashes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum HashId {
+    /// [`HyperLogLog::insert`].
+    SipHash13 = 0,
+    /// `redis::MurmurHash64A`.
+    Redis = 1,
+    /// `datasketches::MurmurHash3`.
+    DataSketches = 2,
+    /// `postgres::MurmurHash3`.
+    Postgres = 3,
+    /// `airlift::MurmurHash3`.
+    Airlift = 4,
+    /// `spark::XxHash64`.
+    Spark = 5,
+    /// `streamlib::MurmurHash64A`.
+    StreamLib = 6,
+    /// `zetasketch::Fingerprint2011`.
+    ZetaSketch = 7,
+}
+
+/// How the registers are encoded.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum Encoding {
+    /// The bytes of the binary serde impls, as written by
+    /// [`HyperLogLog::compress_into`].
+    Compressed = 0,
+    /// One byte per register.
+    Registers = 1,
+}
+
+/// An error reading a [`Sketch`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ProtoError {
+    /// The precision differs from [`Registers::PRECISION`].
+    PrecisionMismatch(u32),
+    /// The hash differs from the one expected.
+    HashMismatch(i32),
+    /// An unrecognized [`Encoding`].
+    UnknownEncoding(i32),
+    /// Raw registers of the wrong length or out of range.
+    InvalidRegisters,
+    /// The compressed payload is invalid.
+    Decompress(DecompressError),
+}
+
+impl fmt::Display for ProtoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PrecisionMismatch(precision) => {
+                write!(f, "hyperloglog proto precision {precision} differs")
+            }
+            Self::HashMismatch(hash) => write!(f, "hyperloglog proto hash {hash} differs"),
+            Self::UnknownEncoding(encoding) => {
+                write!(f, "hyperloglog proto unknown encoding {encoding}")
+            }
+            Self::InvalidRegisters => f.write_str("hyperloglog proto invalid registers"),
+            Self::Decompress(error) => write!(f, "hyperloglog proto payload: {error}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ProtoError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Decompress(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl<R: Registers> HyperLogLog<R> {
+    /// Writes a [`Sketch`] of items hashed by `hash`, with the compressed
+    /// encoding.
+    pub fn to_proto(&self, hash: HashId) -> Sketch {
+        let mut payload = Vec::new();
+        self.compress_into(&mut payload);
+        Sketch {
+            precision: R::PRECISION as u32,
+            hash: hash as i32,
+            encoding: Encoding::Compressed as i32,
+            payload,
+        }
+    }
+
+    /// Reads a [`Sketch`] of items hashed by `hash`, in either encoding.
+    pub fn from_proto(sketch: &Sketch, hash: HashId) -> Result<Self, ProtoError> {
+        if sketch.precision != R::PRECISION as u32 {
+            return Err(ProtoError::PrecisionMismatch(sketch.precision));
+        }
+        if sketch.hash != hash as i32 {
+            return Err(ProtoError::HashMismatch(sketch.hash));
+        }
+        let mut ret = Self::default();
+        match Encoding::try_from(sketch.encoding) {
+            Ok(Encoding::Compressed) => ret
+                .0
+                .decompress(&sketch.payload)
+                .map_err(ProtoError::Decompress)?,
+            Ok(Encoding::Registers) => {
+                if sketch.payload.len() != R::REGISTERS
+                    || sketch.payload.iter().any(|&r| r > R::MAX_VALUE)
+                {
+                    return Err(ProtoError::InvalidRegisters);
+                }
+                ret.0.registers_mut().copy_from_slice(&sketch.payload);
+            }
+            Err(_) => return Err(ProtoError::UnknownEncoding(sketch.encoding)),
+        }
+        Ok(ret)
+    }
+}
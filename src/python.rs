@@ -0,0 +1,154 @@
+//! A Python module wrapping [`HyperLogLog`], for loading the sketches
+//! services persist in notebooks.
+//!
+//! Items are hashed like [`HyperLogLog::insert`] of a `&str` or `&[u8]`, and
+//! sketches load from the bytes of the binary serde impls (bincode's follow
+//! its 8-byte length prefix) or the base64 of the human-readable ones, so
+//! they merge with ones built in Rust.
+//!
+//! Build it with [maturin](https://www.maturin.rs), e.g. `maturin develop`,
+//! with the `python` feature that `pyproject.toml` enables.
+//!
+//! ```python
+//! from hyperloglog import HyperLogLog
+//!
+//! hll = HyperLogLog.from_base64(14, persisted)
+//! hll.update(["/index.html", b"\x01\x02\x03"])
+//! print(len(hll | HyperLogLog(14)))
+//! ```
+//!
+//! [`HyperLogLog`]: crate::HyperLogLog
+//! [`HyperLogLog::insert`]: crate::HyperLogLog::insert
+
+use crate::dynamic::{empty, Sketch};
+use crate::DecompressError;
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyString};
+use std::boxed::Box;
+use std::format;
+use std::string::{String, ToString};
+use std::vec::Vec;
+
+impl From<DecompressError> for PyErr {
+    fn from(error: DecompressError) -> Self {
+        PyValueError::new_err(error.to_string())
+    }
+}
+
+/// A [`HyperLogLog`](crate::HyperLogLog) of a precision chosen at runtime,
+/// exported to Python as `HyperLogLog`.
+#[pyclass(name = "HyperLogLog", module = "hyperloglog")]
+pub struct PyHyperLogLog(Box<dyn Sketch>);
+
+#[pymethods]
+impl PyHyperLogLog {
+    /// An empty sketch with `2^precision` registers, for a precision in
+    /// `4..=18`.
+    #[new]
+    pub fn new(precision: u8) -> PyResult<Self> {
+        empty(precision).map(Self).ok_or_else(|| {
+            PyValueError::new_err(format!("hyperloglog precision {precision} unsupported"))
+        })
+    }
+
+    /// Decodes the output of [`Self::to_bytes`], or of the binary serde
+    /// impls, of a sketch of `precision`.
+    #[staticmethod]
+    pub fn from_bytes(precision: u8, bytes: &[u8]) -> PyResult<Self> {
+        let mut ret = Self::new(precision)?;
+        ret.0.decompress(bytes)?;
+        Ok(ret)
+    }
+
+    /// Decodes the output of [`Self::to_base64`], or of the human-readable
+    /// serde impls, of a sketch of `precision`.
+    #[staticmethod]
+    pub fn from_base64(precision: u8, base64: &str) -> PyResult<Self> {
+        let mut ret = Self::new(precision)?;
+        ret.0.set_base64(base64)?;
+        Ok(ret)
+    }
+
+    #[getter]
+    pub fn precision(&self) -> u8 {
+        self.0.precision()
+    }
+
+    /// Counts a `str` or `bytes` if it is distinct.
+    pub fn insert(&mut self, item: &Bound<'_, PyAny>) -> PyResult<()> {
+        if let Ok(item) = item.downcast::<PyString>() {
+            self.0.insert_str(&item.to_cow()?);
+        } else if let Ok(item) = item.downcast::<PyBytes>() {
+            self.0.insert_bytes(item.as_bytes());
+        } else {
+            return Err(PyTypeError::new_err(format!(
+                "can't insert {}, only str or bytes",
+                item.get_type().name()?
+            )));
+        }
+        Ok(())
+    }
+
+    /// Counts each item of an iterable, like [`Self::insert`].
+    pub fn update(&mut self, items: &Bound<'_, PyAny>) -> PyResult<()> {
+        for item in items.try_iter()? {
+            self.insert(&item?)?;
+        }
+        Ok(())
+    }
+
+    /// Forgets previous insertions.
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    /// The encoding used by the binary serde impls.
+    pub fn to_bytes<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        let mut bytes = Vec::new();
+        self.0.compress_into(&mut bytes);
+        PyBytes::new(py, &bytes)
+    }
+
+    /// The base64 of the human-readable serde impls.
+    pub fn to_base64(&self) -> String {
+        self.0.to_base64()
+    }
+
+    /// Estimates the number of distinct items inserted.
+    pub fn __len__(&self) -> usize {
+        self.0.cardinality() as usize
+    }
+
+    /// A sketch of the items of both, which must have the same precision.
+    pub fn __or__(&self, other: &Self) -> PyResult<Self> {
+        let mut ret = Self::new(self.0.precision())?;
+        ret.0.merge(&*self.0);
+        if !ret.0.merge(&*other.0) {
+            return Err(PyValueError::new_err(format!(
+                "hyperloglog precision {} differs",
+                other.0.precision()
+            )));
+        }
+        Ok(ret)
+    }
+
+    pub fn __getnewargs__(&self) -> (u8,) {
+        (self.0.precision(),)
+    }
+
+    pub fn __getstate__<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        self.to_bytes(py)
+    }
+
+    pub fn __setstate__(&mut self, state: &[u8]) -> PyResult<()> {
+        *self = Self::from_bytes(self.0.precision(), state)?;
+        Ok(())
+    }
+}
+
+/// The `hyperloglog` Python module.
+#[pymodule]
+pub fn hyperloglog(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyHyperLogLog>()
+}
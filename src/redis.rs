@@ -0,0 +1,195 @@
+//! Conversion to and from the string encoding of Redis's `PFADD`/`PFCOUNT`.
+//!
+//! Redis always uses 2^14 registers, so only `HyperLogLog<[u8; 16384]>` can
+//! be converted. Items must be inserted with [`MurmurHash64A`] to stay
+//! compatible with sketches updated by Redis itself.
+
+pub use crate::murmur2::murmurhash64a;
+use crate::{HyperLogLog, ItemHasher, Registers};
+use alloc::vec::Vec;
+use core::fmt;
+
+/// Registers of a Redis HyperLogLog.
+pub type RedisRegisters = [u8; 16384];
+
+const MAGIC: &[u8; 4] = b"HYLL";
+const HEADER_LEN: usize = 16;
+const DENSE: u8 = 0;
+const SPARSE: u8 = 1;
+const DENSE_LEN: usize = HEADER_LEN + (RedisRegisters::REGISTERS * BITS).div_ceil(8);
+const BITS: usize = 6;
+/// `HLL_Q + 1`, the largest value Redis stores in a register.
+const MAX_VALUE: u8 = 64 - 14 + 1;
+/// `HLL_SPARSE_VAL_MAX_VALUE`.
+const SPARSE_MAX_VALUE: u8 = 32;
+/// Default `hll-sparse-max-bytes`, past which Redis switches to dense.
+const SPARSE_MAX_BYTES: usize = 3000;
+/// Seed Redis passes to [`murmurhash64a`].
+const SEED: u64 = 0xadc83b19;
+
+/// An error reading a Redis HyperLogLog string.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RedisError {
+    /// Missing `HYLL` header or unknown encoding.
+    InvalidHeader,
+    /// Dense encoding of the wrong length.
+    InvalidLength,
+    /// Sparse opcodes not covering exactly every register, or a register
+    /// value Redis never produces.
+    InvalidRegisters,
+}
+
+impl fmt::Display for RedisError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::InvalidHeader => "redis hyperloglog invalid header",
+            Self::InvalidLength => "redis hyperloglog invalid length",
+            Self::InvalidRegisters => "redis hyperloglog invalid registers",
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for RedisError {}
+
+impl HyperLogLog<RedisRegisters> {
+    /// Reads the value of a Redis HyperLogLog key, in either the sparse or
+    /// dense encoding.
+    pub fn from_redis(bytes: &[u8]) -> Result<Self, RedisError> {
+        if bytes.len() < HEADER_LEN || &bytes[..4] != MAGIC {
+            return Err(RedisError::InvalidHeader);
+        }
+        let mut ret = Self::default();
+        let registers = ret.0.registers_mut();
+        match bytes[4] {
+            DENSE => {
+                if bytes.len() != DENSE_LEN {
+                    return Err(RedisError::InvalidLength);
+                }
+                let dense = &bytes[HEADER_LEN..];
+                for (i, register) in registers.iter_mut().enumerate() {
+                    *register = get_dense(dense, i);
+                    if *register > MAX_VALUE {
+                        return Err(RedisError::InvalidRegisters);
+                    }
+                }
+            }
+            SPARSE => {
+                let mut sparse = &bytes[HEADER_LEN..];
+                let mut index = 0;
+                while let Some((&opcode, rest)) = sparse.split_first() {
+                    sparse = rest;
+                    let (len, value) = if opcode & 0x80 != 0 {
+                        // VAL: 1vvvvvxx
+                        ((opcode & 0b11) as usize + 1, ((opcode >> 2) & 0b11111) + 1)
+                    } else if opcode & 0x40 != 0 {
+                        // XZERO: 01xxxxxx yyyyyyyy
+                        let (&low, rest) =
+                            sparse.split_first().ok_or(RedisError::InvalidRegisters)?;
+                        sparse = rest;
+                        ((((opcode & 0x3f) as usize) << 8 | low as usize) + 1, 0)
+                    } else {
+                        // ZERO: 00xxxxxx
+                        ((opcode & 0x3f) as usize + 1, 0)
+                    };
+                    registers
+                        .get_mut(index..index + len)
+                        .ok_or(RedisError::InvalidRegisters)?
+                        .fill(value);
+                    index += len;
+                }
+                if index != registers.len() {
+                    return Err(RedisError::InvalidRegisters);
+                }
+            }
+            _ => return Err(RedisError::InvalidHeader),
+        }
+        Ok(ret)
+    }
+
+    /// Writes the value of a Redis HyperLogLog key, choosing between the
+    /// sparse and dense encodings like Redis does.
+    ///
+    /// The cached cardinality is marked stale, so `PFCOUNT` recomputes it.
+    pub fn to_redis(&self) -> Vec<u8> {
+        let registers = self.0.registers();
+        let mut ret = Vec::with_capacity(HEADER_LEN);
+        ret.extend_from_slice(MAGIC);
+        ret.extend_from_slice(&[SPARSE, 0, 0, 0]);
+        // Redis only ever stores a valid cached cardinality of zero without
+        // being asked to count.
+        let stale = registers.iter().any(|&r| r != 0);
+        ret.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, if stale { 0x80 } else { 0 }]);
+
+        if registers.iter().all(|&r| r <= SPARSE_MAX_VALUE) {
+            let mut index = 0;
+            while index < registers.len() && ret.len() <= SPARSE_MAX_BYTES {
+                let value = registers[index];
+                let run = registers[index..]
+                    .iter()
+                    .take_while(|&&r| r == value)
+                    .count();
+                if value != 0 {
+                    let len = run.min(4);
+                    ret.push(0x80 | (value - 1) << 2 | (len - 1) as u8);
+                    index += len;
+                } else if run <= 64 {
+                    ret.push((run - 1) as u8);
+                    index += run;
+                } else {
+                    let len = run.min(1 << 14);
+                    ret.push(0x40 | ((len - 1) >> 8) as u8);
+                    ret.push((len - 1) as u8);
+                    index += len;
+                }
+            }
+            if ret.len() <= SPARSE_MAX_BYTES {
+                return ret;
+            }
+        }
+
+        ret.truncate(HEADER_LEN);
+        ret[4] = DENSE;
+        ret.resize(DENSE_LEN, 0);
+        let dense = &mut ret[HEADER_LEN..];
+        for (i, &register) in registers.iter().enumerate() {
+            set_dense(dense, i, register.min(MAX_VALUE));
+        }
+        ret
+    }
+}
+
+/// Reads the 6-bit register `index`, stored least significant bit first.
+fn get_dense(dense: &[u8], index: usize) -> u8 {
+    let bit = index * BITS;
+    let (byte, shift) = (bit / 8, bit % 8);
+    let mut bits = (dense[byte] >> shift) as u16;
+    if let Some(&next) = dense.get(byte + 1) {
+        bits |= (next as u16) << (8 - shift);
+    }
+    (bits & 0x3f) as u8
+}
+
+fn set_dense(dense: &mut [u8], index: usize, value: u8) {
+    let bit = index * BITS;
+    let (byte, shift) = (bit / 8, bit % 8);
+    let bits = (value as u16) << shift;
+    dense[byte] |= bits as u8;
+    if let Some(next) = dense.get_mut(byte + 1) {
+        *next |= (bits >> 8) as u8;
+    }
+}
+
+/// Hashes items like Redis's `PFADD`: [`murmurhash64a`], register index from
+/// the low bits, and value from the trailing zeros of the rest.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct MurmurHash64A;
+
+impl ItemHasher for MurmurHash64A {
+    fn register(&self, item: &[u8], precision: u8) -> (usize, u8) {
+        let hash = murmurhash64a(item, SEED);
+        let index = hash as usize & ((1 << precision) - 1);
+        let rest = (hash >> precision) | (1 << (64 - precision));
+        (index, rest.trailing_zeros() as u8 + 1)
+    }
+}
@@ -0,0 +1,173 @@
+//! A ring of [`HyperLogLog`]s, one per interval of time, for counting the
+//! distinct items of recent windows, such as hourly, daily, and weekly
+//! uniques from a ring of 168 hourly buckets.
+//!
+//! Unlike [`SlidingHyperLogLog`](crate::sliding::SlidingHyperLogLog), which
+//! can count from any time, windows start and end at bucket boundaries, and
+//! each bucket is an ordinary [`HyperLogLog`].
+
+use crate::{HyperLogLog, Registers};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::hash::Hash;
+use core::ops::{Bound, RangeBounds, RangeInclusive};
+
+/// The [`HyperLogLog`]s of the last few intervals of time.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HyperLogLogRing<R> {
+    interval: u64,
+    /// The bucket of each interval `i` is at `i % buckets.len()`.
+    buckets: Vec<HyperLogLog<R>>,
+    /// The latest interval, whose timestamps are `latest * interval` until
+    /// the next.
+    latest: u64,
+}
+
+impl<R: Registers> HyperLogLogRing<R> {
+    /// A ring of `buckets` empty buckets, each of timestamps `interval`
+    /// apart, starting at timestamp 0.
+    ///
+    /// # Panics
+    ///
+    /// If `interval` or `buckets` is 0.
+    pub fn new(interval: u64, buckets: usize) -> Self {
+        assert!(interval > 0, "interval must be positive");
+        assert!(buckets > 0, "ring must have buckets");
+        Self {
+            interval,
+            buckets: vec![HyperLogLog::default(); buckets],
+            latest: 0,
+        }
+    }
+
+    /// The timestamps each bucket spans.
+    pub fn interval(&self) -> u64 {
+        self.interval
+    }
+
+    /// The number of buckets.
+    pub fn buckets(&self) -> usize {
+        self.buckets.len()
+    }
+
+    /// Count an item, inserted at `timestamp`, if it is distinct, first
+    /// rotating to `timestamp` if it is later than every bucket. Returns
+    /// `false`, and does nothing, if it is earlier than every bucket.
+    pub fn insert_at<V: Hash>(&mut self, item: &V, timestamp: u64) -> bool {
+        self.bucket_mut(timestamp)
+            .map(|bucket| bucket.insert(item))
+            .is_some()
+    }
+
+    /// Like [`HyperLogLog::insert_hash`], for an item inserted at
+    /// `timestamp`, returning what [`Self::insert_at`] does.
+    pub fn insert_hash_at(&mut self, hash: u64, timestamp: u64) -> bool {
+        self.bucket_mut(timestamp)
+            .map(|bucket| bucket.insert_hash(hash))
+            .is_some()
+    }
+
+    /// Advances the ring to end with the bucket of `timestamp`, clearing the
+    /// buckets it reuses. Does nothing if it already ends at or after it.
+    pub fn rotate_to(&mut self, timestamp: u64) {
+        let interval = timestamp / self.interval;
+        if interval <= self.latest {
+            return;
+        }
+        let len = self.buckets.len() as u64;
+        let first = (self.latest + 1).max(interval.saturating_sub(len - 1));
+        for i in first..=interval {
+            self.buckets[(i % len) as usize].clear();
+        }
+        self.latest = interval;
+    }
+
+    /// The bucket of `timestamp`, if it isn't earlier than every bucket.
+    pub fn get(&self, timestamp: u64) -> Option<&HyperLogLog<R>> {
+        let interval = timestamp / self.interval;
+        self.intervals(..)
+            .contains(&interval)
+            .then(|| &self.buckets[(interval % self.buckets.len() as u64) as usize])
+    }
+
+    /// The union of the buckets that overlap `range` of timestamps.
+    pub fn merge_over(&self, range: impl RangeBounds<u64>) -> HyperLogLog<R> {
+        let mut ret = HyperLogLog::default();
+        let len = self.buckets.len() as u64;
+        for interval in self.intervals(range) {
+            ret.merge(&self.buckets[(interval % len) as usize]);
+        }
+        ret
+    }
+
+    /// Estimate the number of distinct items inserted into the buckets that
+    /// overlap `range` of timestamps.
+    pub fn cardinality_over(&self, range: impl RangeBounds<u64>) -> u64 {
+        self.merge_over(range).cardinality()
+    }
+
+    /// Forgets previous insertions, without rotating.
+    pub fn clear(&mut self) {
+        self.buckets.iter_mut().for_each(HyperLogLog::clear);
+    }
+
+    fn bucket_mut(&mut self, timestamp: u64) -> Option<&mut HyperLogLog<R>> {
+        self.rotate_to(timestamp);
+        let interval = timestamp / self.interval;
+        let len = self.buckets.len() as u64;
+        (self.latest - interval < len).then(|| &mut self.buckets[(interval % len) as usize])
+    }
+
+    /// The intervals in the ring that overlap `range` of timestamps.
+    fn intervals(&self, range: impl RangeBounds<u64>) -> RangeInclusive<u64> {
+        let first = self.latest.saturating_sub(self.buckets.len() as u64 - 1);
+        let start = match range.start_bound() {
+            Bound::Included(&start) => start / self.interval,
+            Bound::Excluded(&start) => match start.checked_add(1) {
+                Some(start) => start / self.interval,
+                None => return RangeInclusive::new(1, 0),
+            },
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&end) => end / self.interval,
+            Bound::Excluded(&end) => match end.checked_sub(1) {
+                Some(end) => end / self.interval,
+                None => return RangeInclusive::new(1, 0),
+            },
+            Bound::Unbounded => self.latest,
+        };
+        start.max(first)..=end.min(self.latest)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<R: Registers> serde::Serialize for HyperLogLogRing<R> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serde::Serialize::serialize(&(self.interval, self.latest, &self.buckets), serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, R: Registers> serde::Deserialize<'de> for HyperLogLogRing<R> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let (interval, latest, buckets) =
+            <(u64, u64, Vec<HyperLogLog<R>>) as serde::Deserialize>::deserialize(deserializer)?;
+        if interval == 0 || buckets.is_empty() {
+            return Err(serde::de::Error::custom(
+                "hyperloglog ring without interval or buckets",
+            ));
+        }
+        Ok(Self {
+            interval,
+            buckets,
+            latest,
+        })
+    }
+}
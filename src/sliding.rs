@@ -0,0 +1,109 @@
+//! A [`HyperLogLog`] over a sliding window of time, for counting the
+//! distinct items of the last few minutes, after Chabchoub and Hébrail,
+//! "Sliding HyperLogLog: Estimating cardinality in a data stream over a
+//! sliding window" (2010).
+//!
+//! Instead of only its largest value, each register keeps a list of future
+//! possible maxima: the values inserted into it that no later insertion has
+//! since exceeded, with when they were inserted. The register of a window
+//! that starts at some time is the largest value inserted since then, which
+//! is the first on the list inserted since then.
+
+use crate::{register_of, sip_hash, HyperLogLog, Registers};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::hash::Hash;
+use core::marker::PhantomData;
+
+/// An approximate counter for distinct elements inserted since any time.
+#[derive(Clone, PartialEq, Eq)]
+pub struct SlidingHyperLogLog<R> {
+    /// For each register, `(timestamp, value)` pairs in increasing order of
+    /// timestamp and decreasing order of value.
+    maxima: Vec<Vec<(u64, u8)>>,
+    registers: PhantomData<R>,
+}
+
+impl<R> fmt::Debug for SlidingHyperLogLog<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SlidingHyperLogLog").finish_non_exhaustive()
+    }
+}
+
+impl<R: Registers> Default for SlidingHyperLogLog<R> {
+    fn default() -> Self {
+        Self {
+            maxima: vec![Vec::new(); R::REGISTERS],
+            registers: PhantomData,
+        }
+    }
+}
+
+impl<R: Registers> SlidingHyperLogLog<R> {
+    /// Count an item, inserted at `timestamp`, if it is distinct. Items may
+    /// be inserted out of order.
+    pub fn insert_at<V: Hash>(&mut self, item: &V, timestamp: u64) {
+        self.insert_hash_at(sip_hash(item), timestamp);
+    }
+
+    /// Like [`HyperLogLog::insert_hash`], for an item inserted at
+    /// `timestamp`.
+    pub fn insert_hash_at(&mut self, hash: u64, timestamp: u64) {
+        let (index, value) = register_of(hash, R::PRECISION);
+        self.insert_value(index, timestamp, value);
+    }
+
+    fn insert_value(&mut self, index: usize, timestamp: u64, value: u8) {
+        let maxima = &mut self.maxima[index];
+        let later = maxima.partition_point(|&(t, _)| t < timestamp);
+        if maxima.get(later).is_some_and(|&(_, v)| v >= value) {
+            // A value as large was inserted as late.
+            return;
+        }
+        // Drop the values this one exceeds that were inserted no later.
+        let exceeded = maxima[..later].partition_point(|&(_, v)| v > value);
+        let end = maxima.partition_point(|&(t, _)| t <= timestamp);
+        maxima.splice(exceeded..end, [(timestamp, value)]);
+    }
+
+    /// The counter of the items inserted at or after `timestamp`.
+    pub fn since(&self, timestamp: u64) -> HyperLogLog<R> {
+        let mut ret = HyperLogLog::<R>::default();
+        for (register, maxima) in ret.0.registers_mut().iter_mut().zip(&self.maxima) {
+            let since = maxima.partition_point(|&(t, _)| t < timestamp);
+            *register = maxima.get(since).map_or(0, |&(_, value)| value);
+        }
+        ret
+    }
+
+    /// Estimate the number of distinct items inserted at or after
+    /// `timestamp`.
+    pub fn cardinality_since(&self, timestamp: u64) -> u64 {
+        self.since(timestamp).cardinality()
+    }
+
+    /// Merge the items of another counter, as if they were inserted into
+    /// this one.
+    pub fn merge(&mut self, other: &Self) {
+        for (index, maxima) in other.maxima.iter().enumerate() {
+            for &(timestamp, value) in maxima {
+                self.insert_value(index, timestamp, value);
+            }
+        }
+    }
+
+    /// Forgets the items inserted before `timestamp`, which no window that
+    /// starts at or after it counts.
+    pub fn remove_before(&mut self, timestamp: u64) {
+        for maxima in &mut self.maxima {
+            let since = maxima.partition_point(|&(t, _)| t < timestamp);
+            maxima.drain(..since);
+        }
+    }
+
+    /// Forgets previous insertions.
+    pub fn clear(&mut self) {
+        self.maxima.iter_mut().for_each(Vec::clear);
+    }
+}
@@ -0,0 +1,116 @@
+//! Conversion to and from the aggregation buffer of Spark's
+//! `approx_count_distinct`, the registers of its `HyperLogLogPlusPlus`.
+//!
+//! Registers are 6 bits, packed 10 to a 64-bit word starting from the least
+//! significant bit, and the words are stored little-endian like in an
+//! `UnsafeRow`. The precision is `ceil(2 * log2(1.106 / rsd))` for the
+//! relative standard deviation `rsd`, so 9 for the default of 0.05, and is
+//! implied by the number of words. Items must be inserted with [`XxHash64`]
+//! to stay compatible with buffers updated by Spark.
+
+pub use crate::xxhash::xxhash64;
+use crate::{fold, HyperLogLog, ItemHasher, Registers};
+use alloc::vec::Vec;
+use core::fmt;
+
+/// An error reading a Spark `HyperLogLogPlusPlus` buffer.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SparkError {
+    /// A number of words not matching any precision.
+    InvalidLength,
+    /// The precision is less than [`Registers::PRECISION`].
+    PrecisionTooLow(u8),
+    /// A register value past the largest at the buffer's precision.
+    InvalidRegisters,
+}
+
+impl fmt::Display for SparkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidLength => f.write_str("spark hyperloglog invalid length"),
+            Self::PrecisionTooLow(precision) => {
+                write!(f, "spark hyperloglog precision {precision} too low")
+            }
+            Self::InvalidRegisters => f.write_str("spark hyperloglog invalid registers"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SparkError {}
+
+const REGISTER_SIZE: usize = 6;
+const REGISTERS_PER_WORD: usize = 10;
+const REGISTER_WORD_MASK: u64 = (1 << REGISTER_SIZE) - 1;
+/// Smallest precision Spark accepts.
+const MIN_PRECISION: u8 = 4;
+/// Precision past which Spark's register indices overflow.
+const MAX_PRECISION: u8 = 30;
+/// Seed Spark passes to [`xxhash64`].
+const SEED: u64 = 42;
+
+impl<R: Registers> HyperLogLog<R> {
+    /// Reads a Spark `HyperLogLogPlusPlus` buffer, whose precision must be
+    /// at least [`Registers::PRECISION`].
+    pub fn from_spark(bytes: &[u8]) -> Result<Self, SparkError> {
+        if bytes.len() % 8 != 0 {
+            return Err(SparkError::InvalidLength);
+        }
+        let words = bytes.len() / 8;
+        let precision = (MIN_PRECISION..=MAX_PRECISION)
+            .find(|&p| (1usize << p).div_ceil(REGISTERS_PER_WORD) == words)
+            .ok_or(SparkError::InvalidLength)?;
+        if precision < R::PRECISION {
+            return Err(SparkError::PrecisionTooLow(precision));
+        }
+
+        let mut ret = Self::default();
+        let registers = ret.0.registers_mut();
+        for index in 0..1 << precision {
+            let word = &bytes[index / REGISTERS_PER_WORD * 8..][..8];
+            let word = u64::from_le_bytes(word.try_into().unwrap());
+            let shift = REGISTER_SIZE * (index % REGISTERS_PER_WORD);
+            let value = (word >> shift & REGISTER_WORD_MASK) as u8;
+            if value > 65 - precision {
+                return Err(SparkError::InvalidRegisters);
+            }
+            if value != 0 {
+                let (index, value) = fold(index, value, precision, R::PRECISION);
+                registers[index] = registers[index].max(value);
+            }
+        }
+        Ok(ret)
+    }
+
+    /// Writes a Spark `HyperLogLogPlusPlus` buffer with a precision of
+    /// [`Registers::PRECISION`].
+    pub fn to_spark(&self) -> Vec<u8> {
+        let registers = self.0.registers();
+        let mut ret = Vec::with_capacity(registers.len().div_ceil(REGISTERS_PER_WORD) * 8);
+        for chunk in registers.chunks(REGISTERS_PER_WORD) {
+            let word = chunk.iter().enumerate().fold(0u64, |word, (i, &value)| {
+                word | (value as u64 & REGISTER_WORD_MASK) << (REGISTER_SIZE * i)
+            });
+            ret.extend_from_slice(&word.to_le_bytes());
+        }
+        ret
+    }
+}
+
+/// Hashes items like Spark's `approx_count_distinct`: [`xxhash64`] with a
+/// seed of 42, register index from the high bits, and value from the
+/// leading zeros of the rest.
+///
+/// `int` and `bigint` values hash their little-endian bytes, and `string`
+/// and `binary` values hash their bytes as is.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct XxHash64;
+
+impl ItemHasher for XxHash64 {
+    fn register(&self, item: &[u8], precision: u8) -> (usize, u8) {
+        let hash = xxhash64(item, SEED);
+        let index = (hash >> (64 - precision)) as usize;
+        let rest = hash << precision | 1 << (precision - 1);
+        (index, rest.leading_zeros() as u8 + 1)
+    }
+}
@@ -0,0 +1,332 @@
+//! A file of many [`HyperLogLog`]s, keyed by `u64`, that is memory-mapped
+//! so sketches can be read and updated in place.
+//!
+//! The file starts with a 16-byte header: the magic `HLLS`, a version byte,
+//! the precision, two zero bytes, and the number of sketches as a
+//! little-endian `u64`. An index of the sketches' keys follows, as strictly
+//! increasing little-endian `u64`s, and then each sketch's registers, one
+//! byte each, in the same order.
+//!
+//! Sketches are named by [`key`]s of their names, which can be added to the
+//! store at any time with [`Store::add_key`].
+
+use crate::{sip_hash, HyperLogLog, HyperLogLogRef, ItemHasher, Registers};
+use memmap2::MmapMut;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::hash::Hash;
+use std::io;
+use std::marker::PhantomData;
+use std::ops::{Bound, Range, RangeBounds};
+use std::path::Path;
+use std::vec::Vec;
+
+const MAGIC: &[u8; 4] = b"HLLS";
+const VERSION: u8 = 1;
+const HEADER_LEN: usize = 16;
+const KEY_LEN: usize = 8;
+
+/// An error opening a [`Store`].
+#[derive(Debug)]
+pub enum StoreError {
+    /// Reading, writing or mapping the file failed.
+    Io(io::Error),
+    /// Missing `HLLS` header, or an unknown version.
+    InvalidHeader,
+    /// The precision differs from [`Registers::PRECISION`].
+    PrecisionMismatch(u8),
+    /// Wrong length for the number of sketches.
+    InvalidLength,
+    /// Keys that aren't strictly increasing.
+    UnsortedKeys,
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "hyperloglog store io error: {error}"),
+            Self::InvalidHeader => f.write_str("hyperloglog store invalid header"),
+            Self::PrecisionMismatch(precision) => {
+                write!(f, "hyperloglog store precision {precision} differs")
+            }
+            Self::InvalidLength => f.write_str("hyperloglog store invalid length"),
+            Self::UnsortedKeys => f.write_str("hyperloglog store unsorted keys"),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for StoreError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+/// The key of the sketch named `name`: its 64-bit SipHash, which is the same
+/// in every process.
+pub fn key<V: Hash + ?Sized>(name: &V) -> u64 {
+    sip_hash(name)
+}
+
+/// A memory-mapped file of [`HyperLogLog`]s by key.
+///
+/// Changes are written back by the operating system, or by [`Self::flush`].
+/// The file must not be modified by anything else while it is open.
+pub struct Store<R> {
+    file: File,
+    mmap: MmapMut,
+    len: usize,
+    _registers: PhantomData<R>,
+}
+
+impl<R> fmt::Debug for Store<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Store")
+            .field("len", &self.len)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<R: Registers + bytemuck::Pod> Store<R> {
+    /// Creates a file at `path`, replacing any existing one, with an empty
+    /// sketch for each distinct key.
+    pub fn create(
+        path: impl AsRef<Path>,
+        keys: impl IntoIterator<Item = u64>,
+    ) -> Result<Self, StoreError> {
+        let mut keys = keys.into_iter().collect::<Vec<_>>();
+        keys.sort_unstable();
+        keys.dedup();
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        file.set_len(file_len::<R>(keys.len()) as u64)?;
+        let mut store = Self::map(file, keys.len())?;
+
+        let header = &mut store.mmap[..HEADER_LEN];
+        header[..4].copy_from_slice(MAGIC);
+        header[4] = VERSION;
+        header[5] = R::PRECISION;
+        header[8..].copy_from_slice(&(keys.len() as u64).to_le_bytes());
+        for (entry, key) in store.index_mut().chunks_exact_mut(KEY_LEN).zip(keys) {
+            entry.copy_from_slice(&key.to_le_bytes());
+        }
+        Ok(store)
+    }
+
+    /// Opens an existing file for reading and updating.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, StoreError> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        let mut header = [0; HEADER_LEN];
+        io::Read::read_exact(&mut &file, &mut header).map_err(|error| {
+            if error.kind() == io::ErrorKind::UnexpectedEof {
+                StoreError::InvalidHeader
+            } else {
+                StoreError::Io(error)
+            }
+        })?;
+        if &header[..4] != MAGIC || header[4] != VERSION || header[6..8] != [0, 0] {
+            return Err(StoreError::InvalidHeader);
+        }
+        if header[5] != R::PRECISION {
+            return Err(StoreError::PrecisionMismatch(header[5]));
+        }
+        let len = usize::try_from(u64::from_le_bytes(header[8..].try_into().unwrap()))
+            .map_err(|_| StoreError::InvalidLength)?;
+        let expected = len
+            .checked_mul(KEY_LEN + R::REGISTERS)
+            .and_then(|len| len.checked_add(HEADER_LEN));
+        if expected != Some(file.metadata()?.len() as usize) {
+            return Err(StoreError::InvalidLength);
+        }
+
+        let store = Self::map(file, len)?;
+        let keys = store.keys().collect::<Vec<_>>();
+        if keys.windows(2).any(|pair| pair[0] >= pair[1]) {
+            return Err(StoreError::UnsortedKeys);
+        }
+        Ok(store)
+    }
+
+    fn map(file: File, len: usize) -> Result<Self, StoreError> {
+        // SAFETY: the file is only modified through this mapping, as
+        // documented on `Store`.
+        let mmap = unsafe { MmapMut::map_mut(&file)? };
+        Ok(Self {
+            file,
+            mmap,
+            len,
+            _registers: PhantomData,
+        })
+    }
+
+    /// The number of sketches.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether there are no sketches.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The keys, in increasing order.
+    pub fn keys(&self) -> impl ExactSizeIterator<Item = u64> + '_ {
+        self.index()
+            .chunks_exact(KEY_LEN)
+            .map(|key| u64::from_le_bytes(key.try_into().unwrap()))
+    }
+
+    /// Adds an empty sketch for `key`, growing the file and moving the
+    /// sketches after it. Returns whether there wasn't one already.
+    pub fn add_key(&mut self, key: u64) -> Result<bool, StoreError> {
+        let position = self.partition_point(|k| k < key);
+        if position < self.len && self.key(position) == key {
+            return Ok(false);
+        }
+        let len = self.len;
+        self.file.set_len(file_len::<R>(len + 1) as u64)?;
+        *self = Self::map(self.file.try_clone()?, len)?;
+
+        // Make room for the key and its sketch, moving the sketches before
+        // it by a key, and those after it by a key and a sketch.
+        let blocks = HEADER_LEN + len * KEY_LEN;
+        let split = blocks + position * R::REGISTERS;
+        let end = blocks + len * R::REGISTERS;
+        self.mmap
+            .copy_within(split..end, split + KEY_LEN + R::REGISTERS);
+        self.mmap.copy_within(blocks..split, blocks + KEY_LEN);
+        self.mmap[split + KEY_LEN..][..R::REGISTERS].fill(0);
+        let entry = HEADER_LEN + position * KEY_LEN;
+        self.mmap.copy_within(entry..blocks, entry + KEY_LEN);
+        self.mmap[entry..][..KEY_LEN].copy_from_slice(&key.to_le_bytes());
+
+        self.len = len + 1;
+        self.mmap[8..HEADER_LEN].copy_from_slice(&(self.len as u64).to_le_bytes());
+        Ok(true)
+    }
+
+    /// Borrows the sketch for `key`, if there is one and its registers are
+    /// valid.
+    pub fn get(&self, key: u64) -> Option<HyperLogLogRef<'_, R>> {
+        let position = self.position(key)?;
+        HyperLogLogRef::new(self.block(position)).ok()
+    }
+
+    /// Counts an item in the sketch for `key`, if there is one. Returns
+    /// whether there is.
+    pub fn insert<V: Hash>(&mut self, key: u64, value: &V) -> bool {
+        self.registers_mut(key).map(|r| r.insert(value)).is_some()
+    }
+
+    /// Like [`Self::insert`], but with the item hashed by `hasher`, like
+    /// [`HyperLogLog::insert_with`].
+    pub fn insert_with<H: ItemHasher>(&mut self, key: u64, hasher: &H, item: &[u8]) -> bool {
+        let Some(registers) = self.registers_mut(key) else {
+            return false;
+        };
+        if hasher.ignores(item) {
+            return true;
+        }
+        let (index, value) = hasher.register(item, R::PRECISION);
+        let register = &mut registers.registers_mut()[index];
+        *register = (*register).max(value);
+        true
+    }
+
+    /// Merges `other` into the sketch for `key`, if there is one. Returns
+    /// whether there is.
+    pub fn merge(&mut self, key: u64, other: &HyperLogLog<R>) -> bool {
+        self.registers_mut(key).map(|r| r.merge(&other.0)).is_some()
+    }
+
+    /// Merges the sketches whose keys are in `range`, skipping any with
+    /// invalid registers.
+    pub fn merge_range(&self, range: impl RangeBounds<u64>) -> HyperLogLog<R> {
+        let start = match range.start_bound() {
+            Bound::Included(&start) => self.partition_point(|key| key < start),
+            Bound::Excluded(&start) => self.partition_point(|key| key <= start),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&end) => self.partition_point(|key| key <= end),
+            Bound::Excluded(&end) => self.partition_point(|key| key < end),
+            Bound::Unbounded => self.len,
+        };
+        let mut ret = HyperLogLog::default();
+        for position in start..end.max(start) {
+            if let Ok(hll) = HyperLogLogRef::new(self.block(position)) {
+                ret.merge_ref(hll);
+            }
+        }
+        ret
+    }
+
+    /// Writes changes back to the file.
+    pub fn flush(&self) -> Result<(), StoreError> {
+        Ok(self.mmap.flush()?)
+    }
+
+    fn index(&self) -> &[u8] {
+        &self.mmap[HEADER_LEN..HEADER_LEN + self.len * KEY_LEN]
+    }
+
+    fn index_mut(&mut self) -> &mut [u8] {
+        &mut self.mmap[HEADER_LEN..HEADER_LEN + self.len * KEY_LEN]
+    }
+
+    fn key(&self, position: usize) -> u64 {
+        let entry = &self.index()[position * KEY_LEN..][..KEY_LEN];
+        u64::from_le_bytes(entry.try_into().unwrap())
+    }
+
+    /// The position of the first key for which `pred` is false, given that
+    /// it is true for all keys before and false for all keys after.
+    fn partition_point(&self, pred: impl Fn(u64) -> bool) -> usize {
+        let (mut low, mut high) = (0, self.len);
+        while low < high {
+            let middle = (low + high) / 2;
+            if pred(self.key(middle)) {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+        low
+    }
+
+    fn position(&self, key: u64) -> Option<usize> {
+        let position = self.partition_point(|k| k < key);
+        (position < self.len && self.key(position) == key).then_some(position)
+    }
+
+    fn block_range(&self, position: usize) -> Range<usize> {
+        let start = HEADER_LEN + self.len * KEY_LEN + position * R::REGISTERS;
+        start..start + R::REGISTERS
+    }
+
+    fn block(&self, position: usize) -> &[u8] {
+        &self.mmap[self.block_range(position)]
+    }
+
+    fn registers_mut(&mut self, key: u64) -> Option<&mut R> {
+        let range = self.block_range(self.position(key)?);
+        Some(bytemuck::from_bytes_mut(&mut self.mmap[range]))
+    }
+}
+
+fn file_len<R: Registers>(len: usize) -> usize {
+    HEADER_LEN + len * (KEY_LEN + R::REGISTERS)
+}
@@ -0,0 +1,211 @@
+//! Conversion to and from the bytes of stream-lib's
+//! `HyperLogLogPlus.getBytes()`, read back by `HyperLogLogPlus.Builder.build`.
+//!
+//! Sketches are in either the normal format, whose 5-bit registers are
+//! packed 6 to a big-endian 32-bit word, or the sparse format, a sorted list
+//! of hashes truncated to the sparse precision. Both are folded down to
+//! [`Registers::PRECISION`] when reading. Items must be inserted with
+//! [`MurmurHash64A`] to stay compatible with sketches built by stream-lib.
+
+pub use crate::murmur2::murmurhash64a;
+use crate::{fold, HyperLogLog, ItemHasher, Registers};
+use alloc::vec::Vec;
+use core::fmt;
+
+/// An error reading a serialized stream-lib `HyperLogLogPlus`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StreamLibError {
+    /// Missing header, or an unknown version or format.
+    InvalidHeader,
+    /// The normal precision is less than [`Registers::PRECISION`].
+    PrecisionTooLow(u8),
+    /// Wrong length for the format and precision.
+    InvalidLength,
+    /// Sparse values stream-lib never produces.
+    InvalidRegisters,
+}
+
+impl fmt::Display for StreamLibError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidHeader => f.write_str("stream-lib hyperloglog invalid header"),
+            Self::PrecisionTooLow(precision) => {
+                write!(f, "stream-lib hyperloglog precision {precision} too low")
+            }
+            Self::InvalidLength => f.write_str("stream-lib hyperloglog invalid length"),
+            Self::InvalidRegisters => f.write_str("stream-lib hyperloglog invalid registers"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for StreamLibError {}
+
+/// `-VERSION`, written first to tell apart the original serialization.
+const VERSION: i32 = -2;
+const NORMAL: u32 = 0;
+const SPARSE: u32 = 1;
+const REGISTER_SIZE: usize = 5;
+const REGISTERS_PER_WORD: usize = 6;
+const REGISTER_MASK: u32 = (1 << REGISTER_SIZE) - 1;
+/// Smallest precision stream-lib accepts.
+const MIN_PRECISION: u32 = 4;
+/// Largest sparse precision whose encoded values fit in 32 bits.
+const MAX_SPARSE_PRECISION: u32 = 25;
+/// Seed stream-lib passes to [`murmurhash64a`].
+const SEED: u64 = 0xe17a1465;
+
+impl<R: Registers> HyperLogLog<R> {
+    /// Reads a serialized stream-lib `HyperLogLogPlus` in the normal or
+    /// sparse format, whose normal precision must be at least
+    /// [`Registers::PRECISION`].
+    pub fn from_streamlib(bytes: &[u8]) -> Result<Self, StreamLibError> {
+        let (version, mut data) = bytes
+            .split_first_chunk::<4>()
+            .ok_or(StreamLibError::InvalidHeader)?;
+        if i32::from_be_bytes(*version) != VERSION {
+            return Err(StreamLibError::InvalidHeader);
+        }
+        let mut header = || read_varint(&mut data).ok_or(StreamLibError::InvalidHeader);
+        let (precision, sparse_precision, format) = (header()?, header()?, header()?);
+        if !(MIN_PRECISION..=MAX_SPARSE_PRECISION).contains(&precision) {
+            return Err(StreamLibError::InvalidHeader);
+        }
+        let precision = precision as u8;
+        if precision < R::PRECISION {
+            return Err(StreamLibError::PrecisionTooLow(precision));
+        }
+
+        let mut ret = Self::default();
+        let registers = ret.0.registers_mut();
+        let mut update = |index: usize, value: u8, from: u8| {
+            let (index, value) = fold(index, value, from, R::PRECISION);
+            registers[index] = registers[index].max(value);
+        };
+        match format {
+            NORMAL => {
+                let len = read_varint(&mut data).ok_or(StreamLibError::InvalidLength)?;
+                let words = (1 << precision) / REGISTERS_PER_WORD + 1;
+                if len as usize != words * 4 || data.len() != words * 4 {
+                    return Err(StreamLibError::InvalidLength);
+                }
+                for index in 0..1 << precision {
+                    let word = &data[index / REGISTERS_PER_WORD * 4..][..4];
+                    let word = u32::from_be_bytes(word.try_into().unwrap());
+                    let shift = REGISTER_SIZE * (index % REGISTERS_PER_WORD);
+                    let value = (word >> shift & REGISTER_MASK) as u8;
+                    if value != 0 {
+                        update(index, value, precision);
+                    }
+                }
+            }
+            SPARSE => {
+                if !(precision as u32..=MAX_SPARSE_PRECISION).contains(&sparse_precision) {
+                    return Err(StreamLibError::InvalidHeader);
+                }
+                let sparse_precision = sparse_precision as u8;
+                let extra_mask = (1 << (sparse_precision - precision)) - 1;
+                let count = read_varint(&mut data).ok_or(StreamLibError::InvalidLength)?;
+                let mut value = 0u32;
+                for _ in 0..count {
+                    // Differences between consecutive values wrap around,
+                    // since values sorted by index aren't always increasing.
+                    let difference = read_varint(&mut data).ok_or(StreamLibError::InvalidLength)?;
+                    value = value.wrapping_add(difference);
+                    if value & 1 != 0 {
+                        // The sparse index, whose extra bits are all zero,
+                        // followed by the value past it.
+                        let index = value >> 7;
+                        let zeros = (value >> 1 & 0x3f) as u8;
+                        if index >> sparse_precision != 0
+                            || index & extra_mask != 0
+                            || zeros == 0
+                            || zeros > 65 - sparse_precision
+                        {
+                            return Err(StreamLibError::InvalidRegisters);
+                        }
+                        update(index as usize, zeros, sparse_precision);
+                    } else {
+                        // The sparse index, whose extra bits are not all
+                        // zero.
+                        let index = value >> 1;
+                        if index >> sparse_precision != 0 || index & extra_mask == 0 {
+                            return Err(StreamLibError::InvalidRegisters);
+                        }
+                        update(index as usize, 0, sparse_precision);
+                    }
+                }
+                if !data.is_empty() {
+                    return Err(StreamLibError::InvalidLength);
+                }
+            }
+            _ => return Err(StreamLibError::InvalidHeader),
+        }
+        Ok(ret)
+    }
+
+    /// Writes a serialized stream-lib `HyperLogLogPlus` in the normal
+    /// format, with a precision of [`Registers::PRECISION`] and the sparse
+    /// format disabled, like `new HyperLogLogPlus(p)`.
+    ///
+    /// Register values past 31, which don't fit in 5 bits, are saturated.
+    pub fn to_streamlib(&self) -> Vec<u8> {
+        let registers = self.0.registers();
+        let words = registers.len() / REGISTERS_PER_WORD + 1;
+        let mut ret = Vec::with_capacity(16 + words * 4);
+        ret.extend_from_slice(&VERSION.to_be_bytes());
+        write_varint(&mut ret, R::PRECISION as u32);
+        write_varint(&mut ret, 0);
+        write_varint(&mut ret, NORMAL);
+        write_varint(&mut ret, words as u32 * 4);
+        let mut chunks = registers.chunks(REGISTERS_PER_WORD);
+        for _ in 0..words {
+            let chunk = chunks.next().unwrap_or_default();
+            let word = chunk.iter().enumerate().fold(0u32, |word, (i, &value)| {
+                word | (value as u32).min(REGISTER_MASK) << (REGISTER_SIZE * i)
+            });
+            ret.extend_from_slice(&word.to_be_bytes());
+        }
+        ret
+    }
+}
+
+/// Reads a varint of up to 5 bytes, like stream-lib's `Varint`.
+fn read_varint(bytes: &mut &[u8]) -> Option<u32> {
+    let mut ret = 0u32;
+    for shift in (0..35).step_by(7) {
+        let (&byte, rest) = bytes.split_first()?;
+        *bytes = rest;
+        ret |= ((byte & 0x7f) as u32) << shift;
+        if byte & 0x80 == 0 {
+            return Some(ret);
+        }
+    }
+    None
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u32) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Hashes items like stream-lib's `HyperLogLogPlus.offer`: [`murmurhash64a`]
+/// with a seed of `0xe17a1465`, register index from the high bits, and value
+/// from the leading zeros of the rest.
+///
+/// Byte arrays hash as is, and other objects hash the bytes of their
+/// `toString()` in the platform's default charset.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct MurmurHash64A;
+
+impl ItemHasher for MurmurHash64A {
+    fn register(&self, item: &[u8], precision: u8) -> (usize, u8) {
+        let hash = murmurhash64a(item, SEED);
+        let index = (hash >> (64 - precision)) as usize;
+        let rest = hash << precision | 1 << (precision - 1);
+        (index, rest.leading_zeros() as u8 + 1)
+    }
+}
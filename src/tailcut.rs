@@ -0,0 +1,163 @@
+//! HyperLogLog-TailCut, after Xiao, Zhou, and Chen, "Better with Fewer
+//! Bits: Improving the Performance of Cardinality Estimation of Large Data
+//! Streams" (2017), whose registers take 4 bits each rather than a byte, for
+//! keeping many sketches in memory.
+//!
+//! Each register is stored as its offset from a base shared by all of them,
+//! which is incremented, and every offset decremented, once none of them
+//! are 0. Values more than 15 above the base are cut to 15 above it, which
+//! is rare, since the base is within a few of the typical value, and only
+//! lowers the estimate.
+//!
+//! [`TailCut`] estimates with the same estimator as a [`HyperLogLog`] of
+//! the same [`Registers`], straight from the offsets. It isn't a
+//! [`Registers`] itself, since those lend out their registers as a byte
+//! each, which offsets sharing bytes and a base can't be borrowed as.
+
+use crate::{estimate, register_of, sip_hash, HyperLogLog, Registers};
+use alloc::boxed::Box;
+use alloc::vec;
+use core::fmt;
+use core::hash::Hash;
+use core::marker::PhantomData;
+
+/// The largest offset, which those that would be larger are cut to.
+const MAX_OFFSET: u8 = 15;
+
+/// An approximate counter for distinct elements, with the precision of `R`
+/// in half the memory.
+#[derive(Clone, PartialEq, Eq)]
+pub struct TailCut<R> {
+    base: u8,
+    /// Two offsets per byte, that of the even register in the low 4 bits.
+    offsets: Box<[u8]>,
+    /// The number of offsets that are 0.
+    zeros: usize,
+    registers: PhantomData<R>,
+}
+
+impl<R> fmt::Debug for TailCut<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TailCut").finish_non_exhaustive()
+    }
+}
+
+impl<R: Registers> Default for TailCut<R> {
+    fn default() -> Self {
+        Self {
+            base: 0,
+            offsets: vec![0; R::REGISTERS / 2].into_boxed_slice(),
+            zeros: R::REGISTERS,
+            registers: PhantomData,
+        }
+    }
+}
+
+impl<R: Registers> TailCut<R> {
+    /// Count an item if it is distinct, hashing it like
+    /// [`HyperLogLog::insert`].
+    pub fn insert<V: Hash>(&mut self, v: &V) {
+        self.insert_hash(sip_hash(v));
+    }
+
+    /// Count an item by its 64-bit hash, like [`HyperLogLog::insert_hash`].
+    pub fn insert_hash(&mut self, hash: u64) {
+        let (index, value) = register_of(hash, R::PRECISION);
+        if value <= self.base {
+            return;
+        }
+        let offset = (value - self.base).min(MAX_OFFSET);
+        let old = self.offset(index);
+        if offset <= old {
+            return;
+        }
+        self.set_offset(index, offset);
+        if old == 0 {
+            self.zeros -= 1;
+            while self.zeros == 0 {
+                self.rebase();
+            }
+        }
+    }
+
+    /// Estimate the number of distinct items inserted.
+    pub fn cardinality(&self) -> u64 {
+        let zeros = if self.base == 0 { self.zeros } else { 0 };
+        let registers = (0..R::REGISTERS).map(|index| self.base + self.offset(index));
+        libm::round(estimate(zeros, registers, R::PRECISION)) as u64
+    }
+
+    /// Merge another counter into this one. Registers cut in either stay
+    /// cut.
+    pub fn merge(&mut self, other: &Self) {
+        let base = self.base.max(other.base);
+        for index in 0..R::REGISTERS {
+            let register = (self.base + self.offset(index)).max(other.base + other.offset(index));
+            self.set_offset(index, (register - base).min(MAX_OFFSET));
+        }
+        self.base = base;
+        self.count_zeros();
+        while self.zeros == 0 {
+            self.rebase();
+        }
+    }
+
+    /// The counter with the registers' values, which are lower than they
+    /// would have been if they were cut.
+    pub fn to_hyperloglog(&self) -> HyperLogLog<R> {
+        let mut ret = HyperLogLog::<R>::default();
+        for (index, register) in ret.0.registers_mut().iter_mut().enumerate() {
+            *register = self.base + self.offset(index);
+        }
+        ret
+    }
+
+    /// Forgets previous insertions.
+    pub fn clear(&mut self) {
+        self.base = 0;
+        self.offsets.fill(0);
+        self.zeros = R::REGISTERS;
+    }
+
+    fn offset(&self, index: usize) -> u8 {
+        self.offsets[index / 2] >> (index % 2 * 4) & 0xf
+    }
+
+    fn set_offset(&mut self, index: usize, offset: u8) {
+        let shift = index % 2 * 4;
+        let byte = &mut self.offsets[index / 2];
+        *byte = *byte & !(0xf << shift) | offset << shift;
+    }
+
+    /// Increments the base, and decrements every offset, none of which may
+    /// be 0.
+    fn rebase(&mut self) {
+        self.base += 1;
+        for byte in self.offsets.iter_mut() {
+            *byte -= 0x11;
+        }
+        self.count_zeros();
+    }
+
+    fn count_zeros(&mut self) {
+        self.zeros = (0..R::REGISTERS)
+            .filter(|&index| self.offset(index) == 0)
+            .count();
+    }
+}
+
+/// Cuts the registers more than 15 above the smallest.
+impl<R: Registers> From<&HyperLogLog<R>> for TailCut<R> {
+    fn from(hll: &HyperLogLog<R>) -> Self {
+        let registers = hll.0.registers();
+        let mut ret = Self {
+            base: registers.iter().copied().min().unwrap_or(0),
+            ..Self::default()
+        };
+        for (index, &register) in registers.iter().enumerate() {
+            ret.set_offset(index, (register - ret.base).min(MAX_OFFSET));
+        }
+        ret.zeros = bytecount::count(registers, ret.base);
+        ret
+    }
+}
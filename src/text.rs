@@ -0,0 +1,317 @@
+//! Text encodings for human-readable serializers other than the default
+//! standard base64, for use with `#[serde(with = "...")]`:
+//!
+//! - [`base64_url`]: URL-safe base64 without padding, for URLs and file
+//!   names.
+//! - [`hex`]: lowercase hex, like a Postgres `bytea` hex literal without the
+//!   `\x` prefix.
+//! - [`z85`]: ZeroMQ's Z85, whose output is a quarter longer than its input
+//!   rather than a third, for JSON logs.
+//!
+//! All of them encode the same bytes as the default, and serializers that
+//! aren't human-readable get those bytes as is.
+
+use crate::io::{self, Read, Write};
+use crate::{HyperLogLog, Registers};
+use core::fmt;
+use core::marker::PhantomData;
+use serde::{de, Deserializer, Serializer};
+
+/// URL-safe base64 without padding.
+pub mod base64_url {
+    use super::{Base64Url, HyperLogLog, Registers};
+    use serde::{Deserializer, Serializer};
+
+    pub fn serialize<R, S>(hll: &HyperLogLog<R>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        R: Registers,
+        S: Serializer,
+    {
+        super::serialize::<Base64Url, _, _>(hll, serializer)
+    }
+
+    pub fn deserialize<'de, R, D>(deserializer: D) -> Result<HyperLogLog<R>, D::Error>
+    where
+        R: Registers,
+        D: Deserializer<'de>,
+    {
+        super::deserialize::<Base64Url, _, _>(deserializer)
+    }
+}
+
+/// Lowercase hex. Uppercase is also accepted when deserializing.
+pub mod hex {
+    use super::{Hex, HyperLogLog, Registers};
+    use serde::{Deserializer, Serializer};
+
+    pub fn serialize<R, S>(hll: &HyperLogLog<R>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        R: Registers,
+        S: Serializer,
+    {
+        super::serialize::<Hex, _, _>(hll, serializer)
+    }
+
+    pub fn deserialize<'de, R, D>(deserializer: D) -> Result<HyperLogLog<R>, D::Error>
+    where
+        R: Registers,
+        D: Deserializer<'de>,
+    {
+        super::deserialize::<Hex, _, _>(deserializer)
+    }
+}
+
+/// Z85, with a final group of fewer than 4 bytes written as one more
+/// character than it has bytes, as in Ascii85.
+pub mod z85 {
+    use super::{HyperLogLog, Registers, Z85};
+    use serde::{Deserializer, Serializer};
+
+    pub fn serialize<R, S>(hll: &HyperLogLog<R>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        R: Registers,
+        S: Serializer,
+    {
+        super::serialize::<Z85, _, _>(hll, serializer)
+    }
+
+    pub fn deserialize<'de, R, D>(deserializer: D) -> Result<HyperLogLog<R>, D::Error>
+    where
+        R: Registers,
+        D: Deserializer<'de>,
+    {
+        super::deserialize::<Z85, _, _>(deserializer)
+    }
+}
+
+/// Encodes bytes as they are written, and decodes them as they are read.
+trait Encoding {
+    const NAME: &'static str;
+
+    fn encode<R: Registers>(hll: &HyperLogLog<R>, f: &mut fmt::Formatter<'_>) -> fmt::Result;
+
+    fn decoder(text: &str) -> impl Read;
+}
+
+struct Base64Url;
+
+impl Encoding for Base64Url {
+    const NAME: &'static str = "url-safe base64";
+
+    fn encode<R: Registers>(hll: &HyperLogLog<R>, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut encoder = io::Base64Writer::new(&base64::prelude::BASE64_URL_SAFE_NO_PAD, f);
+        hll.encode(&mut encoder).map_err(|_| fmt::Error)?;
+        encoder.finish()
+    }
+
+    fn decoder(text: &str) -> impl Read {
+        io::Base64Reader::new(&base64::prelude::BASE64_URL_SAFE_NO_PAD, text)
+    }
+}
+
+struct Hex;
+
+impl Encoding for Hex {
+    const NAME: &'static str = "hex";
+
+    fn encode<R: Registers>(hll: &HyperLogLog<R>, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        hll.encode(HexWriter(f)).map_err(|_| fmt::Error)
+    }
+
+    fn decoder(text: &str) -> impl Read {
+        HexReader(text.as_bytes())
+    }
+}
+
+struct HexWriter<'a, 'b>(&'a mut fmt::Formatter<'b>);
+
+impl Write for HexWriter<'_, '_> {
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        for byte in buf {
+            write!(self.0, "{byte:02x}").map_err(|_| io::Error)?;
+        }
+        Ok(())
+    }
+}
+
+struct HexReader<'a>(&'a [u8]);
+
+impl Read for HexReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut len = 0;
+        for out in buf {
+            let (high, low) = match self.0 {
+                [] => break,
+                [high, low, rest @ ..] => {
+                    self.0 = rest;
+                    (high, low)
+                }
+                [_] => return Err(io::Error),
+            };
+            let digit = |c: &u8| (*c as char).to_digit(16).ok_or(io::Error);
+            *out = (digit(high)? << 4 | digit(low)?) as u8;
+            len += 1;
+        }
+        Ok(len)
+    }
+}
+
+struct Z85;
+
+impl Encoding for Z85 {
+    const NAME: &'static str = "z85";
+
+    fn encode<R: Registers>(hll: &HyperLogLog<R>, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut writer = Z85Writer {
+            writer: f,
+            group: [0; 4],
+            len: 0,
+        };
+        hll.encode(&mut writer).map_err(|_| fmt::Error)?;
+        writer.finish()
+    }
+
+    fn decoder(text: &str) -> impl Read {
+        Z85Reader {
+            text: text.as_bytes(),
+            group: [0; 4],
+            start: 0,
+            end: 0,
+        }
+    }
+}
+
+const Z85_ALPHABET: &[u8; 85] =
+    b"0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ.-:+=^!/*?&<>()[]{}@%$#";
+
+struct Z85Writer<'a, 'b> {
+    writer: &'a mut fmt::Formatter<'b>,
+    group: [u8; 4],
+    len: usize,
+}
+
+impl Z85Writer<'_, '_> {
+    /// Writes the first `chars` characters of the group, zero padded.
+    fn write_group(&mut self, chars: usize) -> fmt::Result {
+        self.group[self.len..].fill(0);
+        let mut value = u32::from_be_bytes(self.group);
+        let mut encoded = [0; 5];
+        for c in encoded.iter_mut().rev() {
+            *c = Z85_ALPHABET[(value % 85) as usize];
+            value /= 85;
+        }
+        self.len = 0;
+        // The alphabet is ASCII.
+        self.writer
+            .write_str(core::str::from_utf8(&encoded[..chars]).unwrap())
+    }
+
+    fn finish(mut self) -> fmt::Result {
+        if self.len == 0 {
+            return Ok(());
+        }
+        self.write_group(self.len + 1)
+    }
+}
+
+impl Write for Z85Writer<'_, '_> {
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        for &byte in buf {
+            self.group[self.len] = byte;
+            self.len += 1;
+            if self.len == 4 {
+                self.write_group(5).map_err(|_| io::Error)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Z85Reader<'a> {
+    text: &'a [u8],
+    group: [u8; 4],
+    start: usize,
+    end: usize,
+}
+
+impl Read for Z85Reader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.start == self.end {
+            if self.text.is_empty() {
+                return Ok(0);
+            }
+            // A final group of fewer than 5 characters is padded with the
+            // last character, and decodes to one fewer byte.
+            let (chars, rest) = self.text.split_at(self.text.len().min(5));
+            if chars.len() == 1 {
+                return Err(io::Error);
+            }
+            self.text = rest;
+            let mut value = 0u64;
+            for i in 0..5 {
+                let c = chars.get(i).unwrap_or(&Z85_ALPHABET[84]);
+                let digit = Z85_ALPHABET.iter().position(|a| a == c).ok_or(io::Error)?;
+                value = value * 85 + digit as u64;
+            }
+            let value = u32::try_from(value).map_err(|_| io::Error)?;
+            self.group = value.to_be_bytes();
+            self.start = 0;
+            self.end = chars.len() - 1;
+        }
+        let len = buf.len().min(self.end - self.start);
+        buf[..len].copy_from_slice(&self.group[self.start..][..len]);
+        self.start += len;
+        Ok(len)
+    }
+}
+
+fn serialize<E, R, S>(hll: &HyperLogLog<R>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    E: Encoding,
+    R: Registers,
+    S: Serializer,
+{
+    struct Text<'a, E, R>(&'a HyperLogLog<R>, PhantomData<E>);
+    impl<E: Encoding, R: Registers> fmt::Display for Text<'_, E, R> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            E::encode(self.0, f)
+        }
+    }
+
+    if serializer.is_human_readable() {
+        serializer.collect_str(&Text::<E, R>(hll, PhantomData))
+    } else {
+        serde::Serialize::serialize(hll, serializer)
+    }
+}
+
+fn deserialize<'de, E, R, D>(deserializer: D) -> Result<HyperLogLog<R>, D::Error>
+where
+    E: Encoding,
+    R: Registers,
+    D: Deserializer<'de>,
+{
+    struct Visitor<E, R>(PhantomData<(E, R)>);
+    impl<E: Encoding, R: Registers> de::Visitor<'_> for Visitor<E, R> {
+        type Value = HyperLogLog<R>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "hyperloglog {} str", E::NAME)
+        }
+
+        fn visit_str<Er>(self, v: &str) -> Result<Self::Value, Er>
+        where
+            Er: de::Error,
+        {
+            HyperLogLog::decode_text(E::decoder(v))
+                .ok_or_else(|| Er::custom(format_args!("hyperloglog invalid {}", E::NAME)))?
+                .map_err(Er::custom)
+        }
+    }
+
+    if deserializer.is_human_readable() {
+        deserializer.deserialize_str(Visitor::<E, R>(PhantomData))
+    } else {
+        serde::Deserialize::deserialize(deserializer)
+    }
+}
@@ -0,0 +1,92 @@
+//! A JavaScript class wrapping [`HyperLogLog`], for building sketches in the
+//! browser.
+//!
+//! Its base64 is that of the human-readable serde impls, and items are
+//! hashed like [`HyperLogLog::insert`] of a `&str` or `&[u8]`, so a sketch
+//! built in JavaScript deserializes as a [`HyperLogLog`] of the same
+//! precision and merges with ones built in Rust.
+//!
+//! ```js
+//! const hll = new HyperLogLog(14);
+//! hll.insert("/index.html");
+//! hll.insertBytes(new Uint8Array([1, 2, 3]));
+//! fetch("/sketches", { method: "POST", body: hll.toBase64() });
+//! ```
+//!
+//! [`HyperLogLog`]: crate::HyperLogLog
+//! [`HyperLogLog::insert`]: crate::HyperLogLog::insert
+
+use crate::dynamic::{empty, Sketch};
+use std::boxed::Box;
+use std::format;
+use std::string::String;
+use wasm_bindgen::prelude::*;
+
+/// A [`HyperLogLog`](crate::HyperLogLog) of a precision chosen at runtime,
+/// exported to JavaScript as `HyperLogLog`.
+#[wasm_bindgen(js_name = HyperLogLog)]
+pub struct JsHyperLogLog(Box<dyn Sketch>);
+
+#[wasm_bindgen(js_class = HyperLogLog)]
+impl JsHyperLogLog {
+    /// An empty sketch with `2^precision` registers, for a precision in
+    /// `4..=18`.
+    #[wasm_bindgen(constructor)]
+    pub fn new(precision: u8) -> Result<JsHyperLogLog, JsError> {
+        empty(precision)
+            .map(Self)
+            .ok_or_else(|| JsError::new(&format!("hyperloglog precision {precision} unsupported")))
+    }
+
+    /// Decodes the output of [`Self::to_base64`], or of the human-readable
+    /// serde impls, of a sketch of `precision`.
+    #[wasm_bindgen(js_name = fromBase64)]
+    pub fn from_base64(precision: u8, base64: &str) -> Result<JsHyperLogLog, JsError> {
+        let mut ret = Self::new(precision)?;
+        ret.0.set_base64(base64)?;
+        Ok(ret)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn precision(&self) -> u8 {
+        self.0.precision()
+    }
+
+    /// Counts a string if it is distinct.
+    pub fn insert(&mut self, item: &str) {
+        self.0.insert_str(item);
+    }
+
+    /// Counts bytes if they are distinct.
+    #[wasm_bindgen(js_name = insertBytes)]
+    pub fn insert_bytes(&mut self, item: &[u8]) {
+        self.0.insert_bytes(item);
+    }
+
+    /// Estimates the number of distinct items inserted.
+    pub fn cardinality(&self) -> f64 {
+        self.0.cardinality() as f64
+    }
+
+    /// Merges a sketch of the same precision.
+    pub fn merge(&mut self, other: &JsHyperLogLog) -> Result<(), JsError> {
+        if !self.0.merge(&*other.0) {
+            return Err(JsError::new(&format!(
+                "hyperloglog precision {} differs",
+                other.0.precision()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Forgets previous insertions.
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    /// The base64 of the human-readable serde impls.
+    #[wasm_bindgen(js_name = toBase64)]
+    pub fn to_base64(&self) -> String {
+        self.0.to_base64()
+    }
+}
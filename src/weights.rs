@@ -3659,6 +3659,7 @@ fn test_threshold_data_is_sorted() {
 }
 
 #[test]
+#[allow(clippy::needless_range_loop)]
 fn test_raw_estimate_data_is_sorted() {
     for i in 1..RAW_ESTIMATE_DATA.len() {
         for j in 1..RAW_ESTIMATE_DATA[i].len() {
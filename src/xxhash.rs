@@ -0,0 +1,112 @@
+//! xxHash, shared by the formats that hash items or check bytes with it.
+
+const PRIME64_1: u64 = 0x9e3779b185ebca87;
+const PRIME64_2: u64 = 0xc2b2ae3d27d4eb4f;
+const PRIME64_3: u64 = 0x165667b19e3779f9;
+const PRIME64_4: u64 = 0x85ebca77c2b2ae63;
+const PRIME64_5: u64 = 0x27d4eb2f165667c5;
+
+/// Yann Collet's 64-bit xxHash, XXH64.
+#[cfg(feature = "spark")]
+pub fn xxhash64(key: &[u8], seed: u64) -> u64 {
+    let mut state = Xxh64::new(seed);
+    state.update(key);
+    state.finish()
+}
+
+/// [`xxhash64`] of bytes given a piece at a time.
+#[derive(Clone)]
+pub(crate) struct Xxh64 {
+    seed: u64,
+    lanes: [u64; 4],
+    stripe: [u8; 32],
+    stripe_len: usize,
+    len: u64,
+}
+
+impl Xxh64 {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            lanes: [
+                seed.wrapping_add(PRIME64_1).wrapping_add(PRIME64_2),
+                seed.wrapping_add(PRIME64_2),
+                seed,
+                seed.wrapping_sub(PRIME64_1),
+            ],
+            stripe: [0; 32],
+            stripe_len: 0,
+            len: 0,
+        }
+    }
+
+    pub(crate) fn update(&mut self, mut bytes: &[u8]) {
+        self.len += bytes.len() as u64;
+        while !bytes.is_empty() {
+            let len = bytes.len().min(32 - self.stripe_len);
+            self.stripe[self.stripe_len..][..len].copy_from_slice(&bytes[..len]);
+            self.stripe_len += len;
+            bytes = &bytes[len..];
+            if self.stripe_len == 32 {
+                for (lane, input) in self.lanes.iter_mut().zip(self.stripe.chunks_exact(8)) {
+                    *lane = round(*lane, u64::from_le_bytes(input.try_into().unwrap()));
+                }
+                self.stripe_len = 0;
+            }
+        }
+    }
+
+    pub(crate) fn finish(&self) -> u64 {
+        let mut h = if self.len >= 32 {
+            let lanes = self.lanes;
+            let mut h = lanes[0]
+                .rotate_left(1)
+                .wrapping_add(lanes[1].rotate_left(7))
+                .wrapping_add(lanes[2].rotate_left(12))
+                .wrapping_add(lanes[3].rotate_left(18));
+            for lane in lanes {
+                h = (h ^ round(0, lane))
+                    .wrapping_mul(PRIME64_1)
+                    .wrapping_add(PRIME64_4);
+            }
+            h
+        } else {
+            self.seed.wrapping_add(PRIME64_5)
+        };
+        h = h.wrapping_add(self.len);
+
+        let mut rest = &self.stripe[..self.stripe_len];
+        while let Some((input, tail)) = rest.split_first_chunk::<8>() {
+            h ^= round(0, u64::from_le_bytes(*input));
+            h = h
+                .rotate_left(27)
+                .wrapping_mul(PRIME64_1)
+                .wrapping_add(PRIME64_4);
+            rest = tail;
+        }
+        if let Some((input, tail)) = rest.split_first_chunk::<4>() {
+            h ^= (u32::from_le_bytes(*input) as u64).wrapping_mul(PRIME64_1);
+            h = h
+                .rotate_left(23)
+                .wrapping_mul(PRIME64_2)
+                .wrapping_add(PRIME64_3);
+            rest = tail;
+        }
+        for &byte in rest {
+            h ^= (byte as u64).wrapping_mul(PRIME64_5);
+            h = h.rotate_left(11).wrapping_mul(PRIME64_1);
+        }
+
+        h ^= h >> 33;
+        h = h.wrapping_mul(PRIME64_2);
+        h ^= h >> 29;
+        h = h.wrapping_mul(PRIME64_3);
+        h ^ (h >> 32)
+    }
+}
+
+fn round(acc: u64, input: u64) -> u64 {
+    acc.wrapping_add(input.wrapping_mul(PRIME64_2))
+        .rotate_left(31)
+        .wrapping_mul(PRIME64_1)
+}
@@ -0,0 +1,434 @@
+//! Conversion to and from the HLL++ sketches of ZetaSketch, as returned by
+//! BigQuery's `HLL_COUNT.INIT` and read by `HLL_COUNT.MERGE`.
+//!
+//! Sketches are `AggregatorStateProto` messages holding either a dense
+//! array of registers, at the normal precision, or a sorted list of hashes
+//! truncated to the sparse precision. Both are folded down to
+//! [`Registers::PRECISION`] when reading. Items must be inserted with
+//! [`Fingerprint2011`] to stay compatible with sketches built by BigQuery.
+
+use crate::murmur2::murmurhash64a;
+use crate::{fold, HyperLogLog, ItemHasher, Registers};
+use alloc::vec::Vec;
+use core::fmt;
+
+/// An error reading a ZetaSketch sketch.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ZetaSketchError {
+    /// Malformed protobuf, or not an HLL++ sketch.
+    InvalidProto,
+    /// The normal precision is less than [`Registers::PRECISION`].
+    PrecisionTooLow(u8),
+    /// Registers or sparse values ZetaSketch never produces.
+    InvalidRegisters,
+}
+
+impl fmt::Display for ZetaSketchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidProto => f.write_str("zetasketch invalid proto"),
+            Self::PrecisionTooLow(precision) => {
+                write!(f, "zetasketch precision {precision} too low")
+            }
+            Self::InvalidRegisters => f.write_str("zetasketch invalid registers"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ZetaSketchError {}
+
+/// `AggregatorType.HYPERLOGLOG_PLUS_UNIQUE`.
+const HYPERLOGLOG_PLUS_UNIQUE: u64 = 112;
+const ENCODING_VERSION: u64 = 2;
+/// Smallest normal precision ZetaSketch accepts.
+const MIN_PRECISION: u8 = 10;
+/// Largest sparse precision ZetaSketch accepts.
+const MAX_SPARSE_PRECISION: u8 = 25;
+/// Default difference between the sparse and normal precisions.
+const SPARSE_PRECISION_DELTA: u8 = 5;
+/// Bits of a sparse value holding the register value, when present.
+const RHO_BITS: u8 = 6;
+
+// `AggregatorStateProto` fields.
+const TYPE: u32 = 1;
+const ENCODING_VERSION_FIELD: u32 = 3;
+const VALUE_TYPE: u32 = 4;
+/// The `hyperloglogplus_unique_state` extension.
+const STATE: u32 = 112;
+// `HyperLogLogPlusUniqueStateProto` fields.
+const SPARSE_SIZE: u32 = 2;
+const PRECISION: u32 = 3;
+const SPARSE_PRECISION: u32 = 4;
+const DATA: u32 = 5;
+const SPARSE_DATA: u32 = 6;
+
+impl<R: Registers> HyperLogLog<R> {
+    /// Reads a serialized ZetaSketch HLL++ sketch, whose normal precision
+    /// must be at least [`Registers::PRECISION`].
+    pub fn from_zetasketch(bytes: &[u8]) -> Result<Self, ZetaSketchError> {
+        let mut state = None;
+        for field in Fields(bytes) {
+            match field? {
+                (TYPE, Value::Varint(HYPERLOGLOG_PLUS_UNIQUE)) => {}
+                (ENCODING_VERSION_FIELD, Value::Varint(ENCODING_VERSION)) => {}
+                (TYPE | ENCODING_VERSION_FIELD, _) => return Err(ZetaSketchError::InvalidProto),
+                (STATE, Value::Bytes(bytes)) => state = Some(bytes),
+                (STATE, _) => return Err(ZetaSketchError::InvalidProto),
+                _ => {}
+            }
+        }
+        let state = state.ok_or(ZetaSketchError::InvalidProto)?;
+
+        let (mut precision, mut sparse_precision) = (None, None);
+        let (mut data, mut sparse_data): (&[u8], &[u8]) = (&[], &[]);
+        for field in Fields(state) {
+            match field? {
+                (PRECISION, Value::Varint(p)) => precision = Some(p),
+                (SPARSE_PRECISION, Value::Varint(sp)) => sparse_precision = Some(sp),
+                (DATA, Value::Bytes(bytes)) => data = bytes,
+                (SPARSE_DATA, Value::Bytes(bytes)) => sparse_data = bytes,
+                (PRECISION | SPARSE_PRECISION | DATA | SPARSE_DATA, _) => {
+                    return Err(ZetaSketchError::InvalidProto)
+                }
+                _ => {}
+            }
+        }
+        let precision = match precision {
+            Some(p) if p < R::PRECISION as u64 => {
+                return Err(ZetaSketchError::PrecisionTooLow(p as u8))
+            }
+            Some(p) if p <= MAX_SPARSE_PRECISION as u64 => p as u8,
+            _ => return Err(ZetaSketchError::InvalidProto),
+        };
+
+        let mut ret = Self::default();
+        let registers = ret.0.registers_mut();
+        let mut update = |index: usize, value: u8, from: u8| {
+            let (index, value) = fold(index, value, from, R::PRECISION);
+            registers[index] = registers[index].max(value);
+        };
+
+        if !data.is_empty() {
+            if data.len() != 1 << precision {
+                return Err(ZetaSketchError::InvalidRegisters);
+            }
+            for (index, &value) in data.iter().enumerate() {
+                if value > 65 - precision {
+                    return Err(ZetaSketchError::InvalidRegisters);
+                }
+                if value != 0 {
+                    update(index, value, precision);
+                }
+            }
+        }
+
+        if !sparse_data.is_empty() {
+            let sparse_precision = match sparse_precision {
+                Some(sp) if (precision as u64..=MAX_SPARSE_PRECISION as u64).contains(&sp) => {
+                    sp as u8
+                }
+                _ => return Err(ZetaSketchError::InvalidProto),
+            };
+            let rho_flag = 1 << sparse_precision.max(precision + RHO_BITS);
+            let mut sparse_data = sparse_data;
+            let mut sparse_value = 0u64;
+            while !sparse_data.is_empty() {
+                sparse_value = read_varint(&mut sparse_data)
+                    .and_then(|difference| sparse_value.checked_add(difference))
+                    .ok_or(ZetaSketchError::InvalidProto)?;
+                if sparse_value & rho_flag != 0 {
+                    // The normal index, followed by the value past the
+                    // sparse index, whose extra bits are all zero.
+                    let index = sparse_value & !rho_flag;
+                    let value = (index & ((1 << RHO_BITS) - 1)) as u8;
+                    let index = (index >> RHO_BITS) as usize;
+                    if index >> precision != 0 || value == 0 || value > 65 - sparse_precision {
+                        return Err(ZetaSketchError::InvalidRegisters);
+                    }
+                    update(index, value + sparse_precision - precision, precision);
+                } else {
+                    // The sparse index, whose extra bits are not all zero.
+                    let extra = sparse_precision - precision;
+                    if sparse_value >> sparse_precision != 0
+                        || sparse_value & ((1 << extra) - 1) == 0
+                    {
+                        return Err(ZetaSketchError::InvalidRegisters);
+                    }
+                    update(sparse_value as usize, 0, sparse_precision);
+                }
+            }
+        }
+        Ok(ret)
+    }
+
+    /// Writes a ZetaSketch HLL++ sketch with a normal precision of
+    /// [`Registers::PRECISION`], and the default sparse precision 5 above it.
+    ///
+    /// The registers are always written densely, since the hashes a sparse
+    /// sketch needs aren't kept. `value_type` is the `DefaultOpsType.Id` of
+    /// the inserted values, if any, which BigQuery requires to match when
+    /// merging sketches. It can be copied from another sketch with
+    /// [`value_type`].
+    ///
+    /// # Panics
+    ///
+    /// If [`Registers::PRECISION`] is less than 10, the smallest ZetaSketch
+    /// supports.
+    pub fn to_zetasketch(&self, value_type: Option<i32>) -> Vec<u8> {
+        assert!(R::PRECISION >= MIN_PRECISION, "{}", R::PRECISION);
+        let registers = self.0.registers();
+
+        let mut state = Vec::with_capacity(registers.len() + 16);
+        write_varint_field(&mut state, SPARSE_SIZE, 0);
+        write_varint_field(&mut state, PRECISION, R::PRECISION as u64);
+        write_varint_field(
+            &mut state,
+            SPARSE_PRECISION,
+            (R::PRECISION + SPARSE_PRECISION_DELTA).min(MAX_SPARSE_PRECISION) as u64,
+        );
+        write_bytes_field(&mut state, DATA, registers);
+
+        let mut ret = Vec::with_capacity(state.len() + 16);
+        write_varint_field(&mut ret, TYPE, HYPERLOGLOG_PLUS_UNIQUE);
+        write_varint_field(&mut ret, ENCODING_VERSION_FIELD, ENCODING_VERSION);
+        if let Some(value_type) = value_type {
+            // Negative enum values are sign extended.
+            write_varint_field(&mut ret, VALUE_TYPE, value_type as i64 as u64);
+        }
+        write_bytes_field(&mut ret, STATE, &state);
+        ret
+    }
+}
+
+/// Reads the `DefaultOpsType.Id` of the values inserted into a serialized
+/// ZetaSketch sketch, if it has one.
+pub fn value_type(bytes: &[u8]) -> Result<Option<i32>, ZetaSketchError> {
+    let mut ret = None;
+    for field in Fields(bytes) {
+        if let (VALUE_TYPE, value) = field? {
+            let Value::Varint(value_type) = value else {
+                return Err(ZetaSketchError::InvalidProto);
+            };
+            ret = Some(value_type as i32);
+        }
+    }
+    Ok(ret)
+}
+
+enum Value<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    Fixed,
+}
+
+/// Iterates the fields of a protobuf message.
+struct Fields<'a>(&'a [u8]);
+
+impl<'a> Iterator for Fields<'a> {
+    type Item = Result<(u32, Value<'a>), ZetaSketchError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.0.is_empty() {
+            return None;
+        }
+        let mut field = || {
+            let key = read_varint(&mut self.0)?;
+            let value = match key & 0b111 {
+                0 => Value::Varint(read_varint(&mut self.0)?),
+                1 | 5 => {
+                    let len = if key & 0b111 == 1 { 8 } else { 4 };
+                    self.0 = self.0.get(len..)?;
+                    Value::Fixed
+                }
+                2 => {
+                    let len = usize::try_from(read_varint(&mut self.0)?).ok()?;
+                    let bytes = self.0.get(..len)?;
+                    self.0 = &self.0[len..];
+                    Value::Bytes(bytes)
+                }
+                _ => return None,
+            };
+            Some((u32::try_from(key >> 3).ok()?, value))
+        };
+        let ret = field().ok_or(ZetaSketchError::InvalidProto);
+        if ret.is_err() {
+            self.0 = &[];
+        }
+        Some(ret)
+    }
+}
+
+fn read_varint(bytes: &mut &[u8]) -> Option<u64> {
+    let mut ret = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = bytes.split_first()?;
+        *bytes = rest;
+        ret |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Some(ret);
+        }
+    }
+    None
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn write_varint_field(out: &mut Vec<u8>, field: u32, value: u64) {
+    write_varint(out, (field as u64) << 3);
+    write_varint(out, value);
+}
+
+fn write_bytes_field(out: &mut Vec<u8>, field: u32, bytes: &[u8]) {
+    write_varint(out, (field as u64) << 3 | 2);
+    write_varint(out, bytes.len() as u64);
+    out.extend_from_slice(bytes);
+}
+
+/// Hashes items like ZetaSketch: [`fingerprint2011`], register index from the
+/// high bits, and value from the leading zeros of the rest.
+///
+/// `STRING` and `BYTES` values hash their bytes as is.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Fingerprint2011;
+
+impl ItemHasher for Fingerprint2011 {
+    fn register(&self, item: &[u8], precision: u8) -> (usize, u8) {
+        let hash = fingerprint2011(item);
+        let index = (hash >> (64 - precision)) as usize;
+        let rest = hash << precision | 1 << (precision - 1);
+        (index, rest.leading_zeros() as u8 + 1)
+    }
+}
+
+const K0: u64 = 0xa5b85c5e198ed849;
+const K1: u64 = 0x8d58ac26afe12e47;
+const K2: u64 = 0xc47b6e9e3a970ed3;
+const K3: u64 = 0xc6a4a7935bd1e995;
+
+/// Google's 64-bit Fingerprint2011, as used by ZetaSketch and Guava.
+pub fn fingerprint2011(bytes: &[u8]) -> u64 {
+    let len = bytes.len();
+    let hash = if len <= 32 {
+        murmurhash64a(bytes, K0 ^ K1 ^ K2)
+    } else if len <= 64 {
+        hash_len_33_to_64(bytes)
+    } else {
+        full_fingerprint(bytes)
+    };
+    let u = if len >= 8 { load64(bytes, 0) } else { K0 };
+    let v = if len >= 9 { load64(bytes, len - 8) } else { K0 };
+    let hash = hash128_to_64(hash.wrapping_add(v), u);
+    // 0 and 1 are reserved.
+    if hash <= 1 {
+        hash.wrapping_add(!1)
+    } else {
+        hash
+    }
+}
+
+fn load64(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
+
+fn shift_mix(value: u64) -> u64 {
+    value ^ (value >> 47)
+}
+
+fn hash128_to_64(high: u64, low: u64) -> u64 {
+    let a = shift_mix((low ^ high).wrapping_mul(K3));
+    let b = shift_mix((high ^ a).wrapping_mul(K3));
+    b.wrapping_mul(K3)
+}
+
+fn weak_hash_len_32_with_seeds(bytes: &[u8], offset: usize, a: u64, b: u64) -> (u64, u64) {
+    let w = load64(bytes, offset);
+    let x = load64(bytes, offset + 8);
+    let y = load64(bytes, offset + 16);
+    let z = load64(bytes, offset + 24);
+    let mut a = a.wrapping_add(w);
+    let mut b = b.wrapping_add(a).wrapping_add(z).rotate_right(51);
+    let c = a;
+    a = a.wrapping_add(x).wrapping_add(y);
+    b = b.wrapping_add(a.rotate_right(23));
+    (a.wrapping_add(z), b.wrapping_add(c))
+}
+
+fn hash_len_33_to_64(bytes: &[u8]) -> u64 {
+    let len = bytes.len();
+    let mut z = load64(bytes, 24);
+    let mut a = load64(bytes, 0).wrapping_add(
+        (len as u64)
+            .wrapping_add(load64(bytes, len - 16))
+            .wrapping_mul(K0),
+    );
+    let mut b = a.wrapping_add(z).rotate_right(52);
+    let mut c = a.rotate_right(37);
+    a = a.wrapping_add(load64(bytes, 8));
+    c = c.wrapping_add(a.rotate_right(7));
+    a = a.wrapping_add(load64(bytes, 16));
+    let vf = a.wrapping_add(z);
+    let vs = b.wrapping_add(a.rotate_right(31)).wrapping_add(c);
+
+    a = load64(bytes, 16).wrapping_add(load64(bytes, len - 32));
+    z = load64(bytes, len - 8);
+    b = a.wrapping_add(z).rotate_right(52);
+    c = a.rotate_right(37);
+    a = a.wrapping_add(load64(bytes, len - 24));
+    c = c.wrapping_add(a.rotate_right(7));
+    a = a.wrapping_add(load64(bytes, len - 16));
+    let wf = a.wrapping_add(z);
+    let ws = b.wrapping_add(a.rotate_right(31)).wrapping_add(c);
+
+    let r = shift_mix(
+        vf.wrapping_add(ws)
+            .wrapping_mul(K2)
+            .wrapping_add(wf.wrapping_add(vs).wrapping_mul(K0)),
+    );
+    shift_mix(r.wrapping_mul(K0).wrapping_add(vs)).wrapping_mul(K2)
+}
+
+fn full_fingerprint(bytes: &[u8]) -> u64 {
+    let len = bytes.len();
+    let mut x = load64(bytes, 0);
+    let mut y = load64(bytes, len - 16) ^ K1;
+    let mut z = load64(bytes, len - 56) ^ K0;
+    let mut v = weak_hash_len_32_with_seeds(bytes, len - 64, len as u64, y);
+    let mut w = weak_hash_len_32_with_seeds(bytes, len - 32, (len as u64).wrapping_mul(K1), K0);
+    z = z.wrapping_add(shift_mix(v.1).wrapping_mul(K1));
+    x = z.wrapping_add(x).rotate_right(39).wrapping_mul(K1);
+    y = y.rotate_right(33).wrapping_mul(K1);
+
+    // Hash 64-byte chunks, up to the last multiple of 64 before the end.
+    for offset in (0..(len - 1) & !63).step_by(64) {
+        x = x
+            .wrapping_add(y)
+            .wrapping_add(v.0)
+            .wrapping_add(load64(bytes, offset + 16))
+            .rotate_right(37)
+            .wrapping_mul(K1);
+        y = y
+            .wrapping_add(v.1)
+            .wrapping_add(load64(bytes, offset + 48))
+            .rotate_right(42)
+            .wrapping_mul(K1);
+        x ^= w.1;
+        y ^= v.0;
+        z = (z ^ w.0).rotate_right(33);
+        v = weak_hash_len_32_with_seeds(bytes, offset, v.1.wrapping_mul(K1), x.wrapping_add(w.0));
+        w = weak_hash_len_32_with_seeds(bytes, offset + 32, z.wrapping_add(w.1), y);
+        core::mem::swap(&mut z, &mut x);
+    }
+    hash128_to_64(
+        hash128_to_64(v.0, w.0)
+            .wrapping_add(shift_mix(y).wrapping_mul(K1))
+            .wrapping_add(z),
+        hash128_to_64(v.1, w.1).wrapping_add(x),
+    )
+}
@@ -0,0 +1,139 @@
+use hyperloglog::airlift::{AirliftError, MurmurHash3};
+use hyperloglog::{HyperLogLog, ItemHasher};
+
+/// Sparse v2 with 2^11 buckets and two entries: prefix `00000000011
+/// 000000100000000` with 0 leading zeros past it, and prefix `00000000101
+/// 000000000000000` with 2.
+const SPARSE: &[u8] = b"\x02\x0b\x02\x00\x00\x40\x60\x00\x02\x00\xa0\x00";
+
+/// Dense v2 with 2^4 buckets and a baseline of 2, buckets 1, 2 and 15 set
+/// to 3, 20 and 17, and the rest to the baseline. Bucket 2 overflows by 3.
+const DENSE: &[u8] = b"\x03\x04\x02\x01\xf0\x00\x00\x00\x00\x00\x0f\x01\x00\x02\x00\x03";
+
+/// Sets register `item[0]` to `item[1]`.
+struct Direct;
+
+impl ItemHasher for Direct {
+    fn register(&self, item: &[u8], _precision: u8) -> (usize, u8) {
+        (item[0] as usize, item[1])
+    }
+}
+
+#[test]
+fn airlift_sparse() {
+    let hll = HyperLogLog::<[u8; 2048]>::from_airlift(SPARSE).unwrap();
+    let mut expected = HyperLogLog::<[u8; 2048]>::default();
+    expected.insert_with(&Direct, &[3, 7]);
+    expected.insert_with(&Direct, &[5, 18]);
+    assert_eq!(hll, expected);
+    assert_eq!(hll.to_airlift(), SPARSE);
+
+    let empty = HyperLogLog::<[u8; 2048]>::default().to_airlift();
+    assert_eq!(empty, b"\x02\x0b\x00\x00");
+    assert_eq!(
+        HyperLogLog::from_airlift(&empty),
+        Ok(HyperLogLog::<[u8; 2048]>::default())
+    );
+}
+
+#[test]
+fn airlift_dense() {
+    let hll = HyperLogLog::<[u8; 16]>::from_airlift(DENSE).unwrap();
+    let mut expected = HyperLogLog::<[u8; 16]>::default();
+    for index in 0..16 {
+        expected.insert_with(&Direct, &[index, 2]);
+    }
+    expected.insert_with(&Direct, &[1, 3]);
+    expected.insert_with(&Direct, &[2, 20]);
+    expected.insert_with(&Direct, &[15, 17]);
+    assert_eq!(hll, expected);
+    assert_eq!(hll.to_airlift(), DENSE);
+}
+
+#[test]
+fn airlift_round_trip() {
+    let mut hll = HyperLogLog::<[u8; 2048]>::default();
+    for i in 0..10u64 {
+        hll.insert_with(&MurmurHash3, &i.to_le_bytes());
+    }
+    let bytes = hll.to_airlift();
+    assert_eq!(bytes[0], 2);
+    assert_eq!(HyperLogLog::from_airlift(&bytes), Ok(hll.clone()));
+
+    for i in 10..10000u64 {
+        hll.insert_with(&MurmurHash3, &i.to_le_bytes());
+    }
+    let bytes = hll.to_airlift();
+    assert_eq!(bytes[0], 3);
+    assert_eq!(HyperLogLog::from_airlift(&bytes), Ok(hll.clone()));
+    let error = (hll.cardinality() as f64 - 10000.0).abs() / 10000.0;
+    assert!(error < 0.1, "{error}");
+}
+
+#[test]
+fn airlift_fold() {
+    let mut large = HyperLogLog::<[u8; 8192]>::default();
+    let mut small = HyperLogLog::<[u8; 2048]>::default();
+    for i in 0..20u64 {
+        large.insert_with(&MurmurHash3, &i.to_le_bytes());
+        small.insert_with(&MurmurHash3, &i.to_le_bytes());
+    }
+    // Sparse entries keep more of the hash than either precision.
+    assert_eq!(
+        HyperLogLog::from_airlift(&large.to_airlift()),
+        Ok(small.clone())
+    );
+
+    for i in 20..20000u64 {
+        large.insert_with(&MurmurHash3, &i.to_le_bytes());
+        small.insert_with(&MurmurHash3, &i.to_le_bytes());
+    }
+    let bytes = large.to_airlift();
+    assert_eq!(HyperLogLog::from_airlift(&bytes), Ok(small));
+    assert_eq!(
+        HyperLogLog::<[u8; 16384]>::from_airlift(&bytes),
+        Err(AirliftError::PrecisionTooLow(13))
+    );
+}
+
+#[test]
+fn airlift_invalid() {
+    type Hll = HyperLogLog<[u8; 16]>;
+    assert_eq!(Hll::from_airlift(b"\x02"), Err(AirliftError::InvalidHeader));
+    assert_eq!(
+        Hll::from_airlift(b"\x01\x04"),
+        Err(AirliftError::InvalidHeader)
+    );
+    assert_eq!(
+        Hll::from_airlift(&SPARSE[..SPARSE.len() - 1]),
+        Err(AirliftError::InvalidLength)
+    );
+    assert_eq!(
+        Hll::from_airlift(&DENSE[..DENSE.len() - 1]),
+        Err(AirliftError::InvalidLength)
+    );
+
+    // An overflow for a bucket whose delta isn't 15.
+    let mut bytes = DENSE.to_vec();
+    bytes[13] = 1;
+    assert_eq!(
+        Hll::from_airlift(&bytes),
+        Err(AirliftError::InvalidRegisters)
+    );
+
+    // A bucket past the largest value at precision 4.
+    let mut bytes = DENSE.to_vec();
+    bytes[15] = 45;
+    assert_eq!(
+        Hll::from_airlift(&bytes),
+        Err(AirliftError::InvalidRegisters)
+    );
+    bytes[15] = 44;
+    assert!(Hll::from_airlift(&bytes).is_ok());
+
+    // More leading zeros than are left past the prefix.
+    assert_eq!(
+        Hll::from_airlift(b"\x02\x0b\x01\x00\x27\x00\x00\x00"),
+        Err(AirliftError::InvalidRegisters)
+    );
+}
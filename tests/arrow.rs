@@ -0,0 +1,59 @@
+use arrow_array::{Array, RecordBatch};
+use arrow_schema::extension::ExtensionType;
+use arrow_schema::{DataType, Field, Schema};
+use hyperloglog::arrow::{check_field, field, from_array, merge_array, to_array, HyperLogLogType};
+use hyperloglog::HyperLogLog;
+use std::sync::Arc;
+
+type Hll = HyperLogLog<[u8; 64]>;
+
+fn hll(items: std::ops::Range<u32>) -> Hll {
+    let mut hll = Hll::default();
+    for i in items {
+        hll.insert(&i);
+    }
+    hll
+}
+
+#[test]
+fn arrow_array() {
+    let (a, b) = (hll(0..100), hll(50..150));
+    let array = to_array([Some(&a), None, Some(&b)]);
+    assert_eq!(array.len(), 3);
+    assert!(array.is_null(1));
+    let mut compressed = Vec::new();
+    a.compress_into(&mut compressed);
+    assert_eq!(array.value(0), compressed);
+
+    let sketches = from_array::<[u8; 64]>(&array)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert!(sketches == [Some(a.clone()), None, Some(b.clone())]);
+
+    let mut merged = a;
+    merged.merge(&b);
+    assert!(merge_array(&array).unwrap() == merged);
+}
+
+#[test]
+fn arrow_field() {
+    let field = field::<[u8; 64]>("users", true);
+    assert_eq!(field.data_type(), &DataType::Binary);
+    assert_eq!(field.extension_type_name(), Some("hyperloglog.sketch"));
+    assert_eq!(field.extension_type_metadata(), Some("{\"precision\":6}"));
+    assert_eq!(
+        field.try_extension_type::<HyperLogLogType>().unwrap(),
+        HyperLogLogType::new(6)
+    );
+    assert!(check_field::<[u8; 64]>(&field).is_ok());
+    assert!(check_field::<[u8; 16]>(&field).is_err());
+    assert!(check_field::<[u8; 64]>(&Field::new("users", DataType::Binary, true)).is_err());
+    assert!(HyperLogLogType::new(6)
+        .supports_data_type(&DataType::Utf8)
+        .is_err());
+
+    let schema = Arc::new(Schema::new(vec![field]));
+    let batch =
+        RecordBatch::try_new(schema, vec![Arc::new(to_array([Some(&hll(0..10))]))]).unwrap();
+    assert!(check_field::<[u8; 64]>(batch.schema().field(0)).is_ok());
+}
@@ -0,0 +1,72 @@
+use hyperloglog::{FromBytesError, HyperLogLog, HyperLogLogRef, ItemHasher, Registers};
+
+/// Sets register `item[0]` to `item[1]`.
+struct Direct;
+
+impl ItemHasher for Direct {
+    fn register(&self, item: &[u8], _precision: u8) -> (usize, u8) {
+        (item[0] as usize, item[1])
+    }
+}
+
+fn owned(bytes: &[u8]) -> HyperLogLog<[u8; 256]> {
+    let mut hll = HyperLogLog::default();
+    for (index, &value) in bytes.iter().enumerate() {
+        hll.insert_with(&Direct, &[index as u8, value]);
+    }
+    hll
+}
+
+#[test]
+fn borrowed_views() {
+    // Many register arrays back to back, like in a memory-mapped file.
+    let buffer = (0..4 * 256)
+        .map(|i: usize| (i.wrapping_mul(2654435761) >> 7) as u8 % 8 * (i / 256) as u8)
+        .collect::<Vec<_>>();
+    let views = buffer
+        .chunks_exact(256)
+        .map(|chunk| HyperLogLogRef::<[u8; 256]>::new(chunk).unwrap())
+        .collect::<Vec<_>>();
+
+    let mut merged = HyperLogLog::<[u8; 256]>::default();
+    for (view, chunk) in views.iter().zip(buffer.chunks_exact(256)) {
+        let hll = owned(chunk);
+        assert_eq!(*view, hll);
+        assert_eq!(hll, *view);
+        assert_eq!(view.cardinality(), hll.cardinality());
+        assert_eq!(HyperLogLog::from(*view), hll);
+        merged.merge_ref(*view);
+    }
+    assert_eq!(views[0].cardinality(), 0);
+    assert_eq!(views[0], HyperLogLog::default());
+    assert_ne!(views[1], views[2]);
+    assert_eq!(views[3], HyperLogLogRef::new(&buffer[3 * 256..]).unwrap());
+    assert!(merged.cardinality() >= views[3].cardinality());
+}
+
+#[test]
+fn borrowed_invalid() {
+    type Ref<'a> = HyperLogLogRef<'a, [u8; 256]>;
+    let mut bytes = [0; 257];
+    assert_eq!(Ref::new(&bytes), Err(FromBytesError::InvalidLength));
+    assert_eq!(Ref::new(&bytes[..255]), Err(FromBytesError::InvalidLength));
+
+    bytes[7] = <[u8; 256]>::MAX_VALUE + 1;
+    assert_eq!(
+        Ref::new(&bytes[..256]),
+        Err(FromBytesError::InvalidRegisters)
+    );
+    bytes[7] = <[u8; 256]>::MAX_VALUE;
+    assert!(Ref::new(&bytes[..256]).is_ok());
+}
+
+#[test]
+fn borrowed_large_precision() {
+    let mut hll = HyperLogLog::<[u8; 16384]>::default();
+    for i in 0..100000u32 {
+        hll.insert(&i);
+    }
+    let view = HyperLogLogRef::<[u8; 16384]>::new(hll.registers()).unwrap();
+    assert_eq!(view, hll);
+    assert_eq!(view.cardinality(), hll.cardinality());
+}
@@ -0,0 +1,97 @@
+//! The canonical encoding must never change, so these compare against
+//! golden files, which must not be regenerated.
+
+use hyperloglog::canonical::CanonicalError;
+use hyperloglog::{HyperLogLog, HyperLogLogRef, Registers};
+
+fn hll<R: Registers>(count: u32) -> HyperLogLog<R> {
+    let mut hll = HyperLogLog::default();
+    for i in 0..count {
+        hll.insert(&i);
+    }
+    hll
+}
+
+fn golden<R: Registers>(count: u32, golden: &[u8]) {
+    let hll = hll::<R>(count);
+    assert_eq!(hll.to_canonical(), golden);
+    assert!(HyperLogLog::<R>::from_canonical(golden).unwrap() == hll);
+}
+
+#[test]
+fn canonical_golden() {
+    // Sparse: version 1, precision 4, tag 1, then register 0 set to 4 by
+    // `0 << 6 | 4`, register 4 to 2 by `3 << 6 | 2 = c2 01`, and register 9
+    // to 1 by `4 << 6 | 1 = 81 02`.
+    let p4 = include_bytes!("fixtures/canonical/p4_3.bin");
+    assert_eq!(p4, &[0x01, 0x04, 0x01, 0x04, 0xc2, 0x01, 0x81, 0x02]);
+    let mut registers = [0u8; 16];
+    (registers[0], registers[4], registers[9]) = (4, 2, 1);
+    assert!(HyperLogLogRef::new(&registers).unwrap() == hll::<[u8; 16]>(3));
+    golden::<[u8; 16]>(3, p4);
+
+    golden::<[u8; 64]>(100, include_bytes!("fixtures/canonical/p6_100.bin"));
+    golden::<[u8; 2048]>(100, include_bytes!("fixtures/canonical/p11_100.bin"));
+    golden::<[u8; 2048]>(100000, include_bytes!("fixtures/canonical/p11_100000.bin"));
+
+    // Empty, which is sparse with no pairs.
+    golden::<[u8; 16384]>(0, &[0x01, 0x0e, 0x01]);
+}
+
+#[test]
+fn canonical_dense() {
+    // Every register as 6 bits from the least significant bit, so register 0
+    // set to 5 and the low bits of register 1 set to 3 are `11_000101`, and
+    // the high bits of register 1 and low bits of register 2, set to 1, are
+    // `0001_0000`.
+    let mut registers = [1u8; 16];
+    (registers[0], registers[1]) = (5, 3);
+    let hll = HyperLogLog::from(HyperLogLogRef::new(&registers).unwrap());
+    let canonical = hll.to_canonical();
+    assert_eq!(canonical[..5], [0x01, 0x04, 0x00, 0xc5, 0x10]);
+    assert_eq!(canonical.len(), 3 + 12);
+    assert!(HyperLogLog::<[u8; 16]>::from_canonical(&canonical).unwrap() == hll);
+}
+
+#[test]
+fn canonical_invalid() {
+    type Hll = HyperLogLog<[u8; 16]>;
+    assert_eq!(
+        Hll::from_canonical(&[0x01, 0x04]),
+        Err(CanonicalError::InvalidHeader)
+    );
+    assert_eq!(
+        Hll::from_canonical(&[0x02, 0x04, 0x01]),
+        Err(CanonicalError::InvalidHeader)
+    );
+    assert_eq!(
+        Hll::from_canonical(&[0x01, 0x04, 0x02]),
+        Err(CanonicalError::InvalidHeader)
+    );
+    assert_eq!(
+        Hll::from_canonical(&[0x01, 0x05, 0x01]),
+        Err(CanonicalError::PrecisionMismatch(5))
+    );
+    // A varint with a trailing zero byte.
+    assert_eq!(
+        Hll::from_canonical(&[0x01, 0x04, 0x01, 0x84, 0x00]),
+        Err(CanonicalError::InvalidRegisters)
+    );
+    // Dense, which is longer than sparse for the empty sketch.
+    let mut dense = vec![0x01, 0x04, 0x00];
+    dense.extend_from_slice(&[0; 12]);
+    assert_eq!(
+        Hll::from_canonical(&dense),
+        Err(CanonicalError::InvalidRegisters)
+    );
+    // A register past `MAX_VALUE`.
+    assert_eq!(
+        Hll::from_canonical(&[0x01, 0x04, 0x01, 0x3e]),
+        Err(CanonicalError::InvalidRegisters)
+    );
+    // An index past the last register.
+    assert_eq!(
+        Hll::from_canonical(&[0x01, 0x04, 0x01, 0x81, 0x08]),
+        Err(CanonicalError::InvalidRegisters)
+    );
+}
@@ -0,0 +1,131 @@
+use hyperloglog::checked::{self, Checksum};
+use hyperloglog::{CompressedFormat, DecompressError, HyperLogLog};
+
+fn hll<const N: usize>(count: u32) -> HyperLogLog<[u8; N]>
+where
+    [u8; N]: hyperloglog::Registers,
+{
+    let mut hll = HyperLogLog::default();
+    for i in 0..count {
+        hll.insert(&i);
+    }
+    hll
+}
+
+/// Decodes the serde bytes.
+fn decode<const N: usize>(bytes: &[u8]) -> Result<HyperLogLog<[u8; N]>, DecompressError>
+where
+    [u8; N]: hyperloglog::Registers,
+{
+    let mut bincode = (bytes.len() as u64).to_le_bytes().to_vec();
+    bincode.extend_from_slice(bytes);
+    bincode::deserialize(&bincode).map_err(|error| match error.to_string().as_str() {
+        "hyperloglog checksum mismatch" => DecompressError::ChecksumMismatch,
+        "hyperloglog bytes too short" => DecompressError::Truncated,
+        "hyperloglog bytes invalid" => DecompressError::Invalid,
+        _ => DecompressError::Invalid,
+    })
+}
+
+#[test]
+fn checked_layout() {
+    // The tags, the CRC-32C id, the length of the empty sparse encoding
+    // `ff 01`, that encoding, and the CRC-32C of all that.
+    let mut bytes = Vec::new();
+    hll::<16>(0).compress_checked_into(Checksum::Crc32c, &mut bytes);
+    assert_eq!(
+        bytes,
+        [0xff, 0x02, 0x00, 0x02, 0xff, 0x01, 0x63, 0x78, 0x54, 0x88]
+    );
+    assert_eq!(CompressedFormat::of(&bytes), Ok(CompressedFormat::Checked));
+    assert!(decode::<16>(&bytes).unwrap() == hll::<16>(0));
+
+    // The same with XXH64.
+    let mut bytes = Vec::new();
+    hll::<16>(0).compress_checked_into(Checksum::XxHash64, &mut bytes);
+    assert_eq!(
+        bytes,
+        [0xff, 0x02, 0x01, 0x02, 0xff, 0x01, 0x28, 0xfe, 0x92, 0xeb, 0xd4, 0xe6, 0x1b, 0xd8]
+    );
+    assert!(decode::<16>(&bytes).unwrap() == hll::<16>(0));
+}
+
+#[test]
+fn checked_round_trip() {
+    for checksum in [Checksum::Crc32c, Checksum::XxHash64] {
+        for count in [0, 10, 100000] {
+            let hll = hll::<2048>(count);
+            let mut bytes = vec![1, 2];
+            hll.compress_checked_into(checksum, &mut bytes);
+            let mut written = Vec::new();
+            hll.write_compressed_checked(checksum, &mut written)
+                .unwrap();
+            assert_eq!(bytes[2..], written);
+            assert!(decode::<2048>(&written).unwrap() == hll);
+        }
+    }
+}
+
+#[test]
+fn checked_serde() {
+    let hll = hll::<64>(100);
+    let mut json = Vec::new();
+    checked::crc32c::serialize(&hll, &mut serde_json::Serializer::new(&mut json)).unwrap();
+    let json = String::from_utf8(json).unwrap();
+    // Base64 of `ff 02 00`.
+    assert!(json.starts_with("\"/wIA"), "{json}");
+    let decoded =
+        checked::crc32c::deserialize::<[u8; 64], _>(&mut serde_json::Deserializer::from_str(&json))
+            .unwrap();
+    assert!(decoded == hll);
+    // The default deserializes it too, and the helpers deserialize input
+    // without a checksum.
+    assert!(serde_json::from_str::<HyperLogLog<[u8; 64]>>(&json).unwrap() == hll);
+    let unchecked = serde_json::to_string(&hll).unwrap();
+    let decoded = checked::xxhash64::deserialize::<[u8; 64], _>(
+        &mut serde_json::Deserializer::from_str(&unchecked),
+    )
+    .unwrap();
+    assert!(decoded == hll);
+
+    let mut corrupted = json.into_bytes();
+    corrupted[10] = if corrupted[10] == b'A' { b'B' } else { b'A' };
+    let corrupted = String::from_utf8(corrupted).unwrap();
+    let error = serde_json::from_str::<HyperLogLog<[u8; 64]>>(&corrupted).unwrap_err();
+    assert!(error.to_string().contains("checksum mismatch"), "{error}");
+}
+
+#[test]
+fn checked_corrupted() {
+    // Every single bit flip is detected, rather than decoding to different
+    // registers.
+    for checksum in [Checksum::Crc32c, Checksum::XxHash64] {
+        for count in [10, 10000] {
+            let mut bytes = Vec::new();
+            hll::<2048>(count).compress_checked_into(checksum, &mut bytes);
+            for bit in 0..bytes.len() * 8 {
+                let mut corrupted = bytes.clone();
+                corrupted[bit / 8] ^= 1 << (bit % 8);
+                let error = decode::<2048>(&corrupted).unwrap_err();
+                if bit >= 3 * 8 {
+                    assert!(
+                        matches!(
+                            error,
+                            DecompressError::ChecksumMismatch | DecompressError::Truncated
+                        ),
+                        "{bit} {error:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    let mut bytes = Vec::new();
+    hll::<16>(3).compress_checked_into(Checksum::Crc32c, &mut bytes);
+    assert_eq!(
+        decode::<16>(&bytes[..bytes.len() - 1]),
+        Err(DecompressError::Truncated)
+    );
+    bytes.push(0);
+    assert_eq!(decode::<16>(&bytes), Err(DecompressError::Invalid));
+}
@@ -0,0 +1,143 @@
+use hyperloglog::HyperLogLog;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+fn hll(args: &[&str], stdin: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_hll"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: Output) -> String {
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// A path in a directory of its own for `test`.
+fn temp(test: &str, name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("hll-cli-{test}-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir.join(name)
+}
+
+fn lines(range: std::ops::Range<u32>) -> String {
+    range.map(|i| format!("{i}\n")).collect()
+}
+
+#[test]
+fn cli_count() {
+    let mut expected = HyperLogLog::<[u8; 4096]>::default();
+    for i in 0..300 {
+        expected.insert(&i.to_string().as_bytes());
+    }
+    let input = lines(0..300) + &lines(100..200);
+    let count = stdout(hll(&["count", "-p", "12"], input.as_bytes()));
+    assert_eq!(count, format!("{}\n", expected.cardinality()));
+
+    let fields = "a,1\nb,1\nc,2\nd\n";
+    assert_eq!(
+        stdout(hll(&["count", "-d", ",", "-f", "2"], fields.as_bytes())),
+        "2\n"
+    );
+    assert_eq!(stdout(hll(&["count", "-d", ","], fields.as_bytes())), "4\n");
+}
+
+#[test]
+fn cli_merge_and_convert() {
+    let a = temp("merge", "a.hll");
+    let b = temp("merge", "b.hll");
+    let merged = temp("merge", "merged.hll");
+    let input = temp("merge", "input.txt");
+    fs::write(&input, lines(0..200)).unwrap();
+    stdout(hll(
+        &["count", "-o", a.to_str().unwrap(), input.to_str().unwrap()],
+        b"",
+    ));
+    stdout(hll(
+        &["count", "-e", "checked", "-o", b.to_str().unwrap()],
+        lines(100..400).as_bytes(),
+    ));
+    let count = stdout(hll(
+        &[
+            "merge",
+            "-o",
+            merged.to_str().unwrap(),
+            a.to_str().unwrap(),
+            b.to_str().unwrap(),
+        ],
+        b"",
+    ));
+    assert_eq!(count, stdout(hll(&["count"], lines(0..400).as_bytes())));
+
+    let mut expected = HyperLogLog::<[u8; 16384]>::default();
+    for i in 0..400 {
+        expected.insert(&i.to_string().as_bytes());
+    }
+    let bytes = fs::read(&merged).unwrap();
+    assert_eq!(HyperLogLog::decompress(&bytes), Ok(expected.clone()));
+
+    let base64 = hll(&["convert", "-t", "base64", merged.to_str().unwrap()], b"").stdout;
+    let mut expected_base64 = String::new();
+    expected.write_base64(&mut expected_base64).unwrap();
+    assert_eq!(
+        String::from_utf8(base64.clone()).unwrap(),
+        expected_base64 + "\n"
+    );
+
+    let canonical = hll(&["convert", "-e", "base64", "-t", "canonical"], &base64).stdout;
+    assert_eq!(canonical, expected.to_canonical());
+    let binary = hll(&["convert", "-e", "canonical", "-t", "binary"], &canonical).stdout;
+    assert_eq!(binary, bytes);
+    fs::remove_dir_all(merged.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn cli_inspect() {
+    let mut sketch = HyperLogLog::<[u8; 16]>::default();
+    for hash in [0, 1, 0x10, u64::MAX, 0x8000_0000_0000_0003] {
+        sketch.insert_hash(hash);
+    }
+    let mut bytes = Vec::new();
+    sketch.compress_into(&mut bytes);
+    // Registers 0 and 1 are 61, from 0 and 1 with none of the other 60 bits
+    // set, and the last two set registers 15 and 3 to 1.
+    let inspect = stdout(hll(&["inspect", "-p", "4"], &bytes));
+    assert_eq!(
+        inspect,
+        format!(
+            "precision: 4\n\
+             registers: 16\n\
+             zero registers: 12\n\
+             estimator: LinearCounting\n\
+             raw estimate: {}\n\
+             bias: 0\n\
+             cardinality: {}\n\
+             histogram:\n  0: 12\n  1: 2\n  61: 2\n",
+            sketch.stats().raw_estimate,
+            sketch.cardinality()
+        )
+    );
+}
+
+#[test]
+fn cli_errors() {
+    let output = hll(&["inspect", "-p", "4"], b"\xff\x09");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("hll: stdin: "));
+    assert!(!hll(&["count", "-p", "19"], b"").status.success());
+    assert!(!hll(&["count", "-f", "2"], b"").status.success());
+    assert!(!hll(&["count", "-d", ",", "-f", "0"], b"").status.success());
+    assert!(!hll(&["count", "missing-file"], b"").status.success());
+}
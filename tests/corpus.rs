@@ -0,0 +1,131 @@
+//! Output of `compress` from past versions, which must stay decodable, and
+//! which compressing the same registers must still output exactly. New
+//! fixtures may be added, but existing ones must never be regenerated.
+
+use hyperloglog::{CompressedFormat, HyperLogLog, HyperLogLogRef, Registers};
+
+fn inserted<R: Registers>(count: u32) -> HyperLogLog<R> {
+    let mut hll = HyperLogLog::default();
+    for i in 0..count {
+        hll.insert(&i);
+    }
+    hll
+}
+
+fn check<R: Registers>(data: &[u8], format: CompressedFormat, expected: &HyperLogLog<R>) {
+    assert_eq!(CompressedFormat::of(data), Ok(format));
+    // As serde bytes, prefixed by their length.
+    let mut bincode = (data.len() as u64).to_le_bytes().to_vec();
+    bincode.extend_from_slice(data);
+    assert!(bincode::deserialize::<HyperLogLog<R>>(&bincode).unwrap() == *expected);
+
+    let mut compressed = Vec::new();
+    expected.compress_into(&mut compressed);
+    assert_eq!(compressed, data);
+    #[cfg(feature = "std")]
+    {
+        let mut written = Vec::new();
+        expected.write_compressed(&mut written).unwrap();
+        assert_eq!(written, data);
+    }
+}
+
+macro_rules! fixture {
+    ($name:literal) => {
+        include_bytes!(concat!("fixtures/compressed/", $name, ".bin"))
+    };
+}
+
+#[test]
+fn corpus_inserted() {
+    use CompressedFormat::*;
+
+    check(fixture!("p4_0"), Sparse, &inserted::<[u8; 16]>(0));
+    check(fixture!("p4_3"), Sparse, &inserted::<[u8; 16]>(3));
+    check(
+        fixture!("p4_100"),
+        UntaggedDense,
+        &inserted::<[u8; 16]>(100),
+    );
+    check(
+        fixture!("p6_100"),
+        UntaggedDense,
+        &inserted::<[u8; 64]>(100),
+    );
+    check(
+        fixture!("p11_100"),
+        UntaggedDense,
+        &inserted::<[u8; 2048]>(100),
+    );
+    check(
+        fixture!("p11_100000"),
+        UntaggedDense,
+        &inserted::<[u8; 2048]>(100000),
+    );
+    check(
+        fixture!("p14_1000"),
+        UntaggedDense,
+        &inserted::<[u8; 16384]>(1000),
+    );
+    check(
+        fixture!("p14_1000000"),
+        UntaggedDense,
+        &inserted::<[u8; 16384]>(1000000),
+    );
+}
+
+#[test]
+fn corpus_registers() {
+    const MAX: u8 = <[u8; 16] as Registers>::MAX_VALUE;
+    let hll = |registers: &[u8; 16]| {
+        HyperLogLog::<[u8; 16]>::from(HyperLogLogRef::new(registers).unwrap())
+    };
+
+    // Sparse: `0 << 6 | 4`, `3 << 6 | 2`, `4 << 6 | 1`.
+    let mut registers = [0; 16];
+    (registers[0], registers[4], registers[9]) = (4, 2, 1);
+    assert_eq!(
+        fixture!("p4_3"),
+        &[0xff, 0x01, 0x04, 0xc2, 0x01, 0x81, 0x02]
+    );
+    check(fixture!("p4_3"), CompressedFormat::Sparse, &hll(&registers));
+
+    // Tagged, since the untagged model can't encode `MAX_VALUE`.
+    check(
+        fixture!("p4_max"),
+        CompressedFormat::Dense,
+        &hll(&[MAX; 16]),
+    );
+    // Tagged, since the untagged encoding would start with `0xff`.
+    check(
+        fixture!("p4_high"),
+        CompressedFormat::Dense,
+        &hll(&[MAX - 1; 16]),
+    );
+
+    let mut registers = [1; 16];
+    registers[0] = MAX - 1;
+    check(
+        fixture!("p4_first"),
+        CompressedFormat::UntaggedDense,
+        &hll(&registers),
+    );
+}
+
+#[test]
+fn corpus_unknown() {
+    use hyperloglog::DecompressError;
+
+    assert_eq!(
+        CompressedFormat::of(&[0xff]),
+        Err(DecompressError::Truncated)
+    );
+    assert_eq!(
+        CompressedFormat::of(&[0xff, 0x03, 0x00]),
+        Err(DecompressError::UnknownEncoding(3))
+    );
+    assert_eq!(
+        CompressedFormat::of(&[]),
+        Ok(CompressedFormat::UntaggedDense)
+    );
+}
@@ -0,0 +1,151 @@
+use hyperloglog::datasketches::{murmurhash3_x64_128, DataSketchesError, HllType, MurmurHash3};
+use hyperloglog::HyperLogLog;
+
+/// Compact serialization of a new `HllSketch(12, HLL_4)`.
+const EMPTY: &[u8] = &[0x02, 0x01, 0x07, 0x0c, 0x03, 0x0c, 0x00, 0x00];
+
+/// Compact serialization of `HllSketch(12, HLL_4)` updated with `"a"` and
+/// `"hello"`, in list mode.
+const A_HELLO: &[u8] = &[
+    0x02, 0x01, 0x07, 0x0c, 0x03, 0x08, 0x02, 0x00, 0x2f, 0x82, 0x3b, 0x04, 0xaa, 0xc1, 0x35, 0x04,
+];
+
+#[test]
+fn datasketches_murmurhash3() {
+    assert_eq!(
+        murmurhash3_x64_128(b"", 9001),
+        (0x1e70a32266491bb9, 0x609736b252406b94)
+    );
+    assert_eq!(murmurhash3_x64_128(b"", 0), (0, 0));
+    assert_eq!(
+        murmurhash3_x64_128(b"hello", 0),
+        (0xcbd8a7b341bd9b02, 0x5b1e906a48ae1d19)
+    );
+    assert_eq!(
+        murmurhash3_x64_128(b"0123456789abcdef", 9001),
+        (0x257b60668d289420, 0x7136b9a3e21fb393)
+    );
+    assert_eq!(
+        murmurhash3_x64_128(b"The quick brown fox jumps over the lazy dog", 9001),
+        (0x2f67dcdbc56dbf23, 0x8a0a2fafd6b2155c)
+    );
+    assert_eq!(
+        murmurhash3_x64_128(&1u64.to_le_bytes(), 9001),
+        (0x0b430d7b96fbf22b, 0xe8ea0960d4246765)
+    );
+}
+
+#[test]
+fn datasketches_empty() {
+    let hll = HyperLogLog::<[u8; 4096]>::from_datasketches(EMPTY).unwrap();
+    assert_eq!(hll, HyperLogLog::default());
+    assert_eq!(hll.to_datasketches(HllType::Hll4), EMPTY);
+}
+
+#[test]
+fn datasketches_list() {
+    let mut hll = HyperLogLog::<[u8; 4096]>::default();
+    hll.insert_with(&MurmurHash3, b"a");
+    hll.insert_with(&MurmurHash3, b"hello");
+    // Ignored, like by DataSketches.
+    hll.insert_with(&MurmurHash3, b"");
+    assert_eq!(HyperLogLog::from_datasketches(A_HELLO).unwrap(), hll);
+    assert_eq!(hll.cardinality(), 2);
+
+    // Folding down to a lower precision.
+    let mut hll = HyperLogLog::<[u8; 1024]>::default();
+    hll.insert_with(&MurmurHash3, b"a");
+    hll.insert_with(&MurmurHash3, b"hello");
+    assert_eq!(HyperLogLog::from_datasketches(A_HELLO).unwrap(), hll);
+
+    assert_eq!(
+        HyperLogLog::<[u8; 8192]>::from_datasketches(A_HELLO),
+        Err(DataSketchesError::PrecisionTooLow(12))
+    );
+}
+
+fn test_hll_type<R: hyperloglog::Registers>(hll_type: HllType, expected_len: usize) {
+    let mut hll = HyperLogLog::<R>::default();
+    for i in 0..100000u64 {
+        hll.insert_with(&MurmurHash3, &i.to_le_bytes());
+    }
+    let bytes = hll.to_datasketches(hll_type);
+    assert!(bytes.len() >= expected_len, "{hll_type:?} {}", bytes.len());
+    assert_eq!(HyperLogLog::<R>::from_datasketches(&bytes).unwrap(), hll);
+    let error = (hll.cardinality() as f64 - 100000.0).abs() / 100000.0;
+    assert!(error < 4.0 / (R::REGISTERS as f64).sqrt(), "{error}");
+}
+
+#[test]
+fn datasketches_hll_types() {
+    test_hll_type::<[u8; 4096]>(HllType::Hll8, 40 + 4096);
+    test_hll_type::<[u8; 4096]>(HllType::Hll6, 40 + 4096 * 3 / 4 + 1);
+    test_hll_type::<[u8; 4096]>(HllType::Hll4, 40 + 4096 / 2);
+    test_hll_type::<[u8; 16]>(HllType::Hll4, 40 + 16 / 2);
+}
+
+#[test]
+fn datasketches_hll4_exceptions() {
+    // lgK = 4, curMin = 1, slot 3 has an exception of 20.
+    let mut bytes = vec![0x0a, 0x01, 0x07, 0x04, 0x02, 0x18, 0x01, 0x02];
+    bytes.extend_from_slice(&[0; 24]);
+    bytes.extend_from_slice(&15u32.to_le_bytes());
+    bytes.extend_from_slice(&1u32.to_le_bytes());
+    bytes.extend_from_slice(&[0x00, 0xf1, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+    bytes.extend_from_slice(&(20u32 << 26 | 3).to_le_bytes());
+
+    let hll = HyperLogLog::<[u8; 16]>::from_datasketches(&bytes).unwrap();
+    let hll8 = hll.to_datasketches(HllType::Hll8);
+    assert_eq!(
+        &hll8[40..],
+        &[1, 1, 2, 20, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1]
+    );
+    let hll4 = hll.to_datasketches(HllType::Hll4);
+    assert_eq!(&hll4[40..], &bytes[40..]);
+    assert_eq!(
+        HyperLogLog::<[u8; 16]>::from_datasketches(&hll4).unwrap(),
+        hll
+    );
+
+    // Missing exception.
+    assert_eq!(
+        HyperLogLog::<[u8; 16]>::from_datasketches(&bytes[..bytes.len() - 4]),
+        Err(DataSketchesError::Truncated)
+    );
+}
+
+#[test]
+fn datasketches_invalid() {
+    let mut bytes = EMPTY.to_vec();
+    bytes[2] = 3;
+    assert_eq!(
+        HyperLogLog::<[u8; 4096]>::from_datasketches(&bytes),
+        Err(DataSketchesError::InvalidHeader)
+    );
+    assert_eq!(
+        HyperLogLog::<[u8; 4096]>::from_datasketches(&A_HELLO[..12]),
+        Err(DataSketchesError::Truncated)
+    );
+
+    // Updatable list with a huge `lgArr`.
+    let mut bytes = A_HELLO.to_vec();
+    bytes[4] = 64;
+    bytes[5] = 0;
+    assert_eq!(
+        HyperLogLog::<[u8; 4096]>::from_datasketches(&bytes),
+        Err(DataSketchesError::InvalidHeader)
+    );
+}
+
+#[test]
+fn datasketches_saturated_registers() {
+    // lgK = 12 in HLL_8, with one register of 63.
+    let mut bytes = vec![0x0a, 0x01, 0x07, 0x0c, 0x00, 0x18, 0x00, 0x0a];
+    bytes.extend_from_slice(&[0; 32]);
+    bytes.extend_from_slice(&[0; 4096]);
+    bytes[40 + 7] = 63;
+
+    let hll = HyperLogLog::<[u8; 4096]>::from_datasketches(&bytes).unwrap();
+    let hll8 = hll.to_datasketches(HllType::Hll8);
+    assert_eq!(hll8[40 + 7], 64 - 12 + 1);
+}
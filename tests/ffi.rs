@@ -0,0 +1,92 @@
+use hyperloglog::ffi::*;
+use hyperloglog::HyperLogLog;
+use std::ptr;
+
+#[test]
+fn ffi_round_trip() {
+    let mut hll = HyperLogLog::<[u8; 16384]>::default();
+    let sketch = hll_new(14);
+    assert!(!sketch.is_null());
+    for i in 0..1000u32 {
+        let item = i.to_le_bytes();
+        unsafe { hll_insert(sketch, item.as_ptr(), item.len()) };
+        hll.insert(&item.as_slice());
+    }
+    unsafe { hll_insert(sketch, ptr::null(), 0) };
+    hll.insert(&<&[u8]>::default());
+    unsafe {
+        assert_eq!(hll_precision(sketch), 14);
+        assert_eq!(hll_cardinality(sketch), hll.cardinality());
+    }
+
+    let mut expected = Vec::new();
+    hll.compress_into(&mut expected);
+    let len = unsafe { hll_serialize(sketch, ptr::null_mut(), 0) };
+    assert_eq!(len, expected.len());
+    let mut bytes = vec![0; len];
+    assert_eq!(
+        unsafe { hll_serialize(sketch, bytes.as_mut_ptr(), len) },
+        len
+    );
+    assert_eq!(bytes, expected);
+
+    let decoded = unsafe { hll_deserialize(14, bytes.as_ptr(), bytes.len()) };
+    assert!(!decoded.is_null());
+    unsafe {
+        assert_eq!(hll_cardinality(decoded), hll.cardinality());
+        hll_free(decoded);
+        hll_free(sketch);
+    }
+}
+
+#[test]
+fn ffi_insert_hash() {
+    let mut hll = HyperLogLog::<[u8; 16]>::default();
+    let sketch = hll_new(4);
+    for hash in [0, 1, 0x10, u64::MAX, 0x8000_0000_0000_0003] {
+        unsafe { hll_insert_hash(sketch, hash) };
+        hll.insert_hash(hash);
+    }
+    let mut expected = Vec::new();
+    hll.compress_into(&mut expected);
+    let mut bytes = vec![0; expected.len()];
+    unsafe {
+        hll_serialize(sketch, bytes.as_mut_ptr(), bytes.len());
+        hll_free(sketch);
+    }
+    assert_eq!(bytes, expected);
+    // Registers 0 and 1 from 0 and 1, with none of the other 60 bits set,
+    // then 3 and 15 from the last two, with the first of them set.
+    assert_eq!(expected, [0xff, 0x01, 0x3d, 0x3d, 0x41, 0xc1, 0x05]);
+}
+
+#[test]
+fn ffi_merge() {
+    let a = hll_new(10);
+    let b = hll_new(10);
+    let c = hll_new(11);
+    unsafe {
+        hll_insert(a, b"a".as_ptr(), 1);
+        hll_insert(b, b"b".as_ptr(), 1);
+        assert!(hll_merge(a, b));
+        assert!(hll_merge(a, a));
+        assert_eq!(hll_cardinality(a), 2);
+        assert!(!hll_merge(a, c));
+        assert_eq!(hll_cardinality(a), 2);
+        for sketch in [a, b, c] {
+            hll_free(sketch);
+        }
+        hll_free(ptr::null_mut());
+    }
+}
+
+#[test]
+fn ffi_invalid() {
+    assert!(hll_new(3).is_null());
+    assert!(hll_new(19).is_null());
+    let truncated = [0xff];
+    unsafe {
+        assert!(hll_deserialize(14, truncated.as_ptr(), truncated.len()).is_null());
+        assert!(hll_deserialize(19, ptr::null(), 0).is_null());
+    }
+}
@@ -0,0 +1,111 @@
+use hyperloglog::map::HyperLogLogMap;
+use hyperloglog::HyperLogLog;
+use std::collections::HashMap;
+
+/// Page `p` is visited by the users `0..p * p`.
+fn visits(pages: u32) -> HyperLogLogMap<String, [u8; 1024]> {
+    let mut map = HyperLogLogMap::new();
+    for page in 0..pages {
+        for user in 0..page * page {
+            map.insert(format!("/{page}"), &user);
+        }
+    }
+    map
+}
+
+fn users(users: u32) -> HyperLogLog<[u8; 1024]> {
+    let mut hll = HyperLogLog::default();
+    for user in 0..users {
+        hll.insert(&user);
+    }
+    hll
+}
+
+#[test]
+fn map_cardinality() {
+    let map = visits(40);
+    // Page 0 has no users, so no key.
+    assert_eq!(map.len(), 39);
+    assert_eq!(map.get("/0"), None);
+    assert_eq!(map.cardinality("/0"), 0);
+    for page in 1..40 {
+        let key = format!("/{page}");
+        let expected = users(page * page);
+        assert_eq!(map.get(&key), Some(expected.clone()), "{key}");
+        assert_eq!(map.cardinality(&key), expected.cardinality());
+    }
+    assert_eq!(map.total(), users(39 * 39));
+    assert_eq!(map.total_cardinality(), users(39 * 39).cardinality());
+}
+
+#[test]
+fn map_top_k() {
+    let map = visits(40);
+    let top: Vec<_> = map
+        .top_k(3)
+        .into_iter()
+        .map(|(key, _)| key.as_str())
+        .collect();
+    assert_eq!(top, ["/39", "/38", "/37"]);
+    assert_eq!(map.top_k(100).len(), 39);
+    assert!(map.top_k(0).is_empty());
+
+    // Keys of equal cardinality are in order.
+    let mut ties = HyperLogLogMap::<&str, [u8; 1024]>::new();
+    for key in ["c", "a", "b"] {
+        ties.insert(key, &1u32);
+    }
+    assert_eq!(ties.keys().collect::<Vec<_>>(), [&"a", &"b", &"c"]);
+    assert_eq!(ties.top_k(2), [(&"a", 1), (&"b", 1)]);
+}
+
+#[test]
+fn map_merge() {
+    let mut map = visits(20);
+    let mut other = HyperLogLogMap::new();
+    for page in 10..40 {
+        for user in 0..page * page {
+            other.insert(format!("/{page}"), &user);
+        }
+    }
+    map.merge(&other);
+    assert_eq!(map, visits(40));
+
+    let mut dense = HyperLogLogMap::<_, [u8; 1024]>::new();
+    for user in 0..1000u32 {
+        dense.insert("/1".to_string(), &user);
+    }
+    let mut sparse = visits(3);
+    sparse.merge(&dense);
+    assert_eq!(sparse.get("/1"), Some(users(1000)));
+    dense.merge(&visits(3));
+    assert_eq!(dense, sparse);
+
+    assert_eq!(sparse.remove("/1"), Some(users(1000)));
+    assert_eq!(sparse.keys().collect::<Vec<_>>(), ["/2"]);
+}
+
+#[test]
+fn map_serde() {
+    let map = visits(40);
+    let json = serde_json::to_string(&map).unwrap();
+    assert_eq!(
+        serde_json::from_str::<HyperLogLogMap<_, _>>(&json).unwrap(),
+        map
+    );
+    let bytes = bincode::serialize(&map).unwrap();
+    assert_eq!(
+        bincode::deserialize::<HyperLogLogMap<_, _>>(&bytes).unwrap(),
+        map
+    );
+
+    // It reads a map of sketches maintained by hand.
+    let by_hand: HashMap<String, HyperLogLog<[u8; 1024]>> = serde_json::from_str(&json).unwrap();
+    assert_eq!(by_hand.len(), 39);
+    assert_eq!(by_hand["/12"], users(144));
+    let json = serde_json::to_string(&by_hand).unwrap();
+    assert_eq!(
+        serde_json::from_str::<HyperLogLogMap<_, _>>(&json).unwrap(),
+        map
+    );
+}
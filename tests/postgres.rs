@@ -0,0 +1,177 @@
+use hyperloglog::postgres::{murmurhash3_x64_128, MurmurHash3, PostgresError};
+use hyperloglog::{HyperLogLog, ItemHasher, Registers};
+
+/// `hll_empty()`.
+const EMPTY: &[u8] = b"\x11\x8b\x7f";
+
+/// `hll_add(hll_empty(), hll_hash_integer(1))`.
+const EXPLICIT_1: &[u8] = b"\x12\x8b\x7f\x88\x95\xa3\xf5\xaf\x28\xca\xfe";
+
+/// `EXPLICIT_1`, promoted to `SPARSE`.
+const SPARSE_1: &[u8] = b"\x13\x8b\x7f\x5f\xc1";
+
+/// `EXPLICIT` hashes `0x1111111111111111` and `0x2222222222222222`, from the
+/// extension's regression tests.
+const EXPLICIT_2: &[u8] =
+    b"\x12\x8b\x7f\x11\x11\x11\x11\x11\x11\x11\x11\x22\x22\x22\x22\x22\x22\x22\x22";
+
+/// `SPARSE` register 0 set to 1, from the extension's regression tests.
+const SPARSE_0_1: &[u8] = b"\x13\x8b\x7f\x00\x01";
+
+/// Derived by hand from the storage specification, for parameters other
+/// than the defaults.
+///
+/// `FULL` with `log2m = 4` and `regwidth = 6`, registers
+/// `[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 20, 30, 40, 50, 61]`:
+/// `000000 000001 000010 000011 000100 000101 000110 000111 001000 001001
+/// 001010 010100 011110 101000 110010 111101`.
+const FULL_4_6: &[u8] = b"\x14\xa4\x7f\x00\x10\x83\x10\x51\x87\x20\x92\x94\x7a\x8c\xbd";
+
+/// Derived by hand like `FULL_4_6`.
+///
+/// `SPARSE` with `log2m = 5` and `regwidth = 4`, registers 1, 7 and 30 set
+/// to 3, 15 and 1: `00001 0011 00111 1111 11110 0001` and 5 bits of padding.
+const SPARSE_5_4: &[u8] = b"\x13\x65\x7f\x09\x9f\xfc\x20";
+
+/// Sets register `item[0]` to `item[1]`.
+struct Direct;
+
+impl ItemHasher for Direct {
+    fn register(&self, item: &[u8], _precision: u8) -> (usize, u8) {
+        (item[0] as usize, item[1])
+    }
+}
+
+fn with_registers<R: Registers>(registers: &[(u8, u8)]) -> HyperLogLog<R> {
+    let mut hll = HyperLogLog::default();
+    for &(index, value) in registers {
+        hll.insert_with(&Direct, &[index, value]);
+    }
+    hll
+}
+
+#[test]
+fn postgres_empty() {
+    let hll = HyperLogLog::<[u8; 2048]>::from_postgres(EMPTY).unwrap();
+    assert_eq!(hll, HyperLogLog::default());
+    assert_eq!(hll.to_postgres(5), EMPTY);
+}
+
+#[test]
+fn postgres_explicit_and_sparse() {
+    let mut hll = HyperLogLog::<[u8; 2048]>::default();
+    hll.insert_with(&MurmurHash3, &1i32.to_le_bytes());
+    assert_eq!(HyperLogLog::from_postgres(EXPLICIT_1).unwrap(), hll);
+    assert_eq!(HyperLogLog::from_postgres(SPARSE_1).unwrap(), hll);
+    assert_eq!(hll.to_postgres(5), SPARSE_1);
+    assert_eq!(hll.cardinality(), 1);
+
+    // Explicit hashes can be added at any precision.
+    let mut hll = HyperLogLog::<[u8; 1024]>::default();
+    hll.insert_with(&MurmurHash3, &1i32.to_le_bytes());
+    assert_eq!(HyperLogLog::from_postgres(EXPLICIT_1).unwrap(), hll);
+    assert_eq!(
+        HyperLogLog::<[u8; 1024]>::from_postgres(SPARSE_1),
+        Err(PostgresError::PrecisionMismatch(11))
+    );
+}
+
+#[test]
+fn postgres_hash() {
+    // `hll_hash_integer(1)` and `hll_hash_bigint(1)`.
+    assert_eq!(
+        murmurhash3_x64_128(&1i32.to_le_bytes(), 0).0 as i64,
+        -8604791237420463362
+    );
+    assert_eq!(
+        murmurhash3_x64_128(&1i64.to_le_bytes(), 0).0 as i64,
+        19144387141682250
+    );
+}
+
+#[test]
+fn postgres_regression() {
+    // Registers 273 and 546, set to 2 and 3 by the trailing zeros above the
+    // index bits.
+    let explicit = HyperLogLog::<[u8; 2048]>::from_postgres(EXPLICIT_2).unwrap();
+    assert_eq!(explicit.to_postgres(5), b"\x13\x8b\x7f\x22\x22\x44\x43");
+    assert_eq!(explicit.cardinality(), 2);
+
+    let sparse = HyperLogLog::<[u8; 2048]>::from_postgres(SPARSE_0_1).unwrap();
+    assert_eq!(sparse, with_registers(&[(0, 1)]));
+    assert_eq!(sparse.to_postgres(5), SPARSE_0_1);
+    assert_eq!(sparse.cardinality(), 1);
+}
+
+#[test]
+fn postgres_full() {
+    let mut hll = HyperLogLog::<[u8; 2048]>::default();
+    for i in 0..100000i64 {
+        hll.insert_with(&MurmurHash3, &i.to_le_bytes());
+    }
+    for regwidth in [5, 6, 8] {
+        let bytes = hll.to_postgres(regwidth);
+        assert_eq!(bytes[0], 0x14);
+        assert_eq!(bytes.len(), 3 + 2048 * regwidth as usize / 8);
+        assert_eq!(HyperLogLog::from_postgres(&bytes).unwrap(), hll);
+    }
+    let error = (hll.cardinality() as f64 - 100000.0).abs() / 100000.0;
+    assert!(error < 0.1, "{error}");
+
+    let mut sparse = HyperLogLog::<[u8; 2048]>::default();
+    for i in 0..100i64 {
+        sparse.insert_with(&MurmurHash3, &i.to_le_bytes());
+    }
+    let bytes = sparse.to_postgres(5);
+    assert_eq!(bytes[0], 0x13);
+    assert_eq!(HyperLogLog::from_postgres(&bytes).unwrap(), sparse);
+}
+
+#[test]
+fn postgres_fixtures() {
+    let full = with_registers::<[u8; 16]>(
+        &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 20, 30, 40, 50, 61]
+            .into_iter()
+            .enumerate()
+            .map(|(i, value)| (i as u8, value))
+            .collect::<Vec<_>>(),
+    );
+    assert_eq!(HyperLogLog::from_postgres(FULL_4_6).unwrap(), full);
+    assert_eq!(full.to_postgres(6), FULL_4_6);
+
+    let sparse = with_registers::<[u8; 32]>(&[(1, 3), (7, 15), (30, 1)]);
+    assert_eq!(HyperLogLog::from_postgres(SPARSE_5_4).unwrap(), sparse);
+    assert_eq!(sparse.to_postgres(4), SPARSE_5_4);
+}
+
+#[test]
+fn postgres_invalid() {
+    let mut full = FULL_4_6.to_vec();
+    *full.last_mut().unwrap() = 0xbf;
+    assert_eq!(
+        HyperLogLog::<[u8; 16]>::from_postgres(&full),
+        Err(PostgresError::InvalidRegisters)
+    );
+    // Register 1 set to 62 with `regwidth = 6`.
+    assert_eq!(
+        HyperLogLog::<[u8; 16]>::from_postgres(b"\x13\xa4\x7f\x1f\x80"),
+        Err(PostgresError::InvalidRegisters)
+    );
+
+    assert_eq!(
+        HyperLogLog::<[u8; 2048]>::from_postgres(b"\x11\x8b"),
+        Err(PostgresError::InvalidHeader)
+    );
+    assert_eq!(
+        HyperLogLog::<[u8; 2048]>::from_postgres(b"\x21\x8b\x7f"),
+        Err(PostgresError::InvalidHeader)
+    );
+    assert_eq!(
+        HyperLogLog::<[u8; 2048]>::from_postgres(&EXPLICIT_1[..10]),
+        Err(PostgresError::InvalidLength)
+    );
+    assert_eq!(
+        HyperLogLog::<[u8; 2048]>::from_postgres(b"\x14\x8b\x7f\x00"),
+        Err(PostgresError::InvalidLength)
+    );
+}
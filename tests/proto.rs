@@ -0,0 +1,65 @@
+use hyperloglog::proto::{Encoding, HashId, ProtoError, Sketch};
+use hyperloglog::{DecompressError, HyperLogLog};
+use prost::Message;
+
+#[test]
+fn proto_round_trip() {
+    let mut hll = HyperLogLog::<[u8; 16]>::default();
+    for i in 0..3u32 {
+        hll.insert(&i);
+    }
+    let sketch = hll.to_proto(HashId::SipHash13);
+    let mut payload = Vec::new();
+    hll.compress_into(&mut payload);
+    assert_eq!(sketch.payload, payload);
+
+    // The precision, then the payload, with the hash and encoding left out
+    // as they are zero.
+    let bytes = sketch.encode_to_vec();
+    assert_eq!(bytes[..4], [0x08, 0x04, 0x22, payload.len() as u8]);
+    assert_eq!(bytes[4..], payload);
+    let decoded = Sketch::decode(&*bytes).unwrap();
+    assert_eq!(decoded.hash(), HashId::SipHash13);
+    assert_eq!(decoded.encoding(), Encoding::Compressed);
+    assert!(HyperLogLog::from_proto(&decoded, HashId::SipHash13).unwrap() == hll);
+}
+
+#[test]
+fn proto_registers() {
+    // Precision 4, Spark's hash, registers 0 and 15 set to 1 and 61.
+    let mut bytes = vec![0x08, 0x04, 0x10, 0x05, 0x18, 0x01, 0x22, 0x10];
+    bytes.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 61]);
+    let sketch = Sketch::decode(&*bytes).unwrap();
+    let hll = HyperLogLog::<[u8; 16]>::from_proto(&sketch, HashId::Spark).unwrap();
+    let mut expected = Vec::new();
+    hll.compress_into(&mut expected);
+    // Sparse: `0 << 6 | 1`, then `(15 - 1) << 6 | 61 = 957`.
+    assert_eq!(expected, [0xff, 0x01, 0x01, 0xbd, 0x07]);
+
+    let mut sketch = hll.to_proto(HashId::Spark);
+    assert_eq!(
+        HyperLogLog::<[u8; 32]>::from_proto(&sketch, HashId::Spark),
+        Err(ProtoError::PrecisionMismatch(4))
+    );
+    assert_eq!(
+        HyperLogLog::<[u8; 16]>::from_proto(&sketch, HashId::Redis),
+        Err(ProtoError::HashMismatch(5))
+    );
+    sketch.encoding = 9;
+    assert_eq!(
+        HyperLogLog::<[u8; 16]>::from_proto(&sketch, HashId::Spark),
+        Err(ProtoError::UnknownEncoding(9))
+    );
+    sketch.encoding = Encoding::Compressed as i32;
+    sketch.payload = vec![0xff];
+    assert_eq!(
+        HyperLogLog::<[u8; 16]>::from_proto(&sketch, HashId::Spark),
+        Err(ProtoError::Decompress(DecompressError::Truncated))
+    );
+    sketch.encoding = Encoding::Registers as i32;
+    sketch.payload = vec![62; 16];
+    assert_eq!(
+        HyperLogLog::<[u8; 16]>::from_proto(&sketch, HashId::Spark),
+        Err(ProtoError::InvalidRegisters)
+    );
+}
@@ -0,0 +1,130 @@
+use hyperloglog::python::hyperloglog as module;
+use hyperloglog::HyperLogLog;
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict};
+use pyo3::wrap_pymodule;
+use std::ffi::CStr;
+
+/// Runs `f` with the embedded interpreter, starting it first if needed.
+fn with_gil<T>(f: impl for<'py> FnOnce(Python<'py>) -> T) -> T {
+    pyo3::prepare_freethreaded_python();
+    Python::with_gil(f)
+}
+
+/// The globals of a script that imported the module, which pickle finds in
+/// `sys.modules`.
+fn globals(py: Python<'_>) -> Bound<'_, PyDict> {
+    let module = wrap_pymodule!(module)(py);
+    py.import("sys")
+        .unwrap()
+        .getattr("modules")
+        .unwrap()
+        .set_item("hyperloglog", &module)
+        .unwrap();
+    let globals = PyDict::new(py);
+    globals.set_item("hyperloglog", module).unwrap();
+    globals
+}
+
+/// Runs `code`, returning what it assigns to `ret`.
+fn run<T: for<'py> FromPyObject<'py>>(code: &CStr) -> T {
+    with_gil(|py| {
+        let globals = globals(py);
+        py.run(code, Some(&globals), None).unwrap();
+        globals.get_item("ret").unwrap().unwrap().extract().unwrap()
+    })
+}
+
+#[test]
+fn python_matches_serde() {
+    let mut hll = HyperLogLog::<[u8; 16384]>::default();
+    for i in 0..500 {
+        hll.insert(&i.to_string().as_str());
+        hll.insert(&i.to_string().as_bytes());
+    }
+    let mut expected = Vec::new();
+    hll.compress_into(&mut expected);
+
+    let (bytes, base64, len): (Vec<u8>, String, usize) = run(cr#"
+hll = hyperloglog.HyperLogLog(14)
+hll.update(str(i) for i in range(500))
+for i in range(500):
+    hll.insert(str(i).encode())
+ret = (hll.to_bytes(), hll.to_base64(), len(hll))
+"#);
+    assert_eq!(bytes, expected);
+    assert_eq!(HyperLogLog::from_base64(&base64), Ok(hll.clone()));
+    assert_eq!(len as u64, hll.cardinality());
+}
+
+#[test]
+fn python_load() {
+    let mut hll = HyperLogLog::<[u8; 4096]>::default();
+    for i in 0..100u32 {
+        hll.insert(&i);
+    }
+    let bincode = bincode::serialize(&hll).unwrap();
+    let json = serde_json::to_string(&hll).unwrap();
+    let (from_bincode, from_base64): (usize, usize) = with_gil(|py| {
+        let globals = globals(py);
+        globals
+            .set_item("bincode", PyBytes::new(py, &bincode))
+            .unwrap();
+        globals.set_item("json", json).unwrap();
+        py.run(
+            cr#"
+import json as j
+HLL = hyperloglog.HyperLogLog
+ret = (len(HLL.from_bytes(12, bincode[8:])), len(HLL.from_base64(12, j.loads(json))))
+"#,
+            Some(&globals),
+            None,
+        )
+        .unwrap();
+        globals.get_item("ret").unwrap().unwrap().extract().unwrap()
+    });
+    assert_eq!(from_bincode as u64, hll.cardinality());
+    assert_eq!(from_base64 as u64, hll.cardinality());
+}
+
+#[test]
+fn python_merge_and_pickle() {
+    let ok: bool = run(cr#"
+import pickle
+HLL = hyperloglog.HyperLogLog
+a, b = HLL(10), HLL(10)
+a.update(["a", "b", "c"])
+b.update(["c", "d"])
+merged = a | b
+copy = pickle.loads(pickle.dumps(merged))
+ret = (
+    len(merged) == 4
+    and len(a) == 3
+    and copy.precision == 10
+    and copy.to_bytes() == merged.to_bytes()
+)
+"#);
+    assert!(ok);
+}
+
+#[test]
+fn python_errors() {
+    let ok: bool = run(cr#"
+HLL = hyperloglog.HyperLogLog
+def raises(error, f):
+    try:
+        f()
+    except error:
+        return True
+    return False
+ret = (
+    raises(ValueError, lambda: HLL(3))
+    and raises(ValueError, lambda: HLL(10) | HLL(11))
+    and raises(TypeError, lambda: HLL(10) | 1)
+    and raises(TypeError, lambda: HLL(10).insert(1))
+    and raises(ValueError, lambda: HLL.from_bytes(10, b"\xff\x09"))
+    and raises(ValueError, lambda: HLL.from_base64(10, "!"))
+)
+"#);
+    assert!(ok);
+}
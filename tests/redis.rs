@@ -0,0 +1,79 @@
+use hyperloglog::redis::{murmurhash64a, MurmurHash64A, RedisError};
+use hyperloglog::HyperLogLog;
+
+/// `PFADD key` on a new key.
+const EMPTY: &[u8] = b"HYLL\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x7f\xff";
+
+/// `PFADD key a hello` on a new key.
+const A_HELLO: &[u8] =
+    b"HYLL\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x80\x63\xff\x80\x4d\xa5\x84\x4e\x57";
+
+#[test]
+fn redis_murmurhash64a() {
+    assert_eq!(murmurhash64a(b"", 0xadc83b19), 0xd8dfea6585bc9732);
+    assert_eq!(murmurhash64a(b"a", 0xadc83b19), 0x53d2470a9b43b1a7);
+    assert_eq!(murmurhash64a(b"hello", 0xadc83b19), 0x0f656f01eecfe400);
+    assert_eq!(murmurhash64a(b"foobar123", 0xadc83b19), 0xce43c1791ca0c27e);
+    assert_eq!(
+        murmurhash64a(b"The quick brown fox", 0xadc83b19),
+        0xb8cb2a48ba03f3e4
+    );
+}
+
+#[test]
+fn redis_empty() {
+    let hll = HyperLogLog::from_redis(EMPTY).unwrap();
+    assert_eq!(hll, HyperLogLog::default());
+    assert_eq!(hll.cardinality(), 0);
+    assert_eq!(hll.to_redis(), EMPTY);
+}
+
+#[test]
+fn redis_sparse() {
+    let mut hll = HyperLogLog::default();
+    hll.insert_with(&MurmurHash64A, b"a");
+    hll.insert_with(&MurmurHash64A, b"hello");
+    hll.insert_with(&MurmurHash64A, b"a");
+    assert_eq!(hll.cardinality(), 2);
+    assert_eq!(hll.to_redis(), A_HELLO);
+    assert_eq!(HyperLogLog::from_redis(A_HELLO).unwrap(), hll);
+}
+
+#[test]
+fn redis_dense() {
+    let mut hll = HyperLogLog::default();
+    for i in 0..100000u32 {
+        hll.insert_with(&MurmurHash64A, i.to_string().as_bytes());
+    }
+    let redis = hll.to_redis();
+    assert_eq!(&redis[..5], b"HYLL\x00");
+    assert_eq!(redis.len(), 16 + 12288);
+    assert_eq!(HyperLogLog::from_redis(&redis).unwrap(), hll);
+
+    let error = (hll.cardinality() as f64 - 100000.0).abs() / 100000.0;
+    assert!(error < 0.05, "{error}");
+}
+
+#[test]
+fn redis_invalid() {
+    assert_eq!(
+        HyperLogLog::from_redis(b"HYLL"),
+        Err(RedisError::InvalidHeader)
+    );
+    assert_eq!(
+        HyperLogLog::from_redis(&[&EMPTY[..4], b"\x02", &EMPTY[5..]].concat()),
+        Err(RedisError::InvalidHeader)
+    );
+    assert_eq!(
+        HyperLogLog::from_redis(&EMPTY[..EMPTY.len() - 1]),
+        Err(RedisError::InvalidRegisters)
+    );
+    assert_eq!(
+        HyperLogLog::from_redis(&[EMPTY, b"\x00"].concat()),
+        Err(RedisError::InvalidRegisters)
+    );
+    assert_eq!(
+        HyperLogLog::from_redis(b"HYLL\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00"),
+        Err(RedisError::InvalidLength)
+    );
+}
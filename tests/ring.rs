@@ -0,0 +1,81 @@
+use hyperloglog::ring::HyperLogLogRing;
+use hyperloglog::HyperLogLog;
+
+const HOUR: u64 = 3600;
+
+/// 24 hourly buckets, where hour `h` saw the items `h * 10..h * 10 + 20`.
+fn ring(hours: u64) -> HyperLogLogRing<[u8; 1024]> {
+    let mut ring = HyperLogLogRing::new(HOUR, 24);
+    for hour in 0..hours {
+        for item in hour * 10..hour * 10 + 20 {
+            assert!(ring.insert_at(&item, hour * HOUR + item % HOUR));
+        }
+    }
+    ring
+}
+
+fn expected(items: std::ops::Range<u64>) -> HyperLogLog<[u8; 1024]> {
+    let mut hll = HyperLogLog::default();
+    for item in items {
+        hll.insert(&item);
+    }
+    hll
+}
+
+#[test]
+fn ring_cardinality_over() {
+    let ring = ring(30);
+    // Hours 6 to 29 are still in the ring.
+    assert_eq!(ring.merge_over(..), expected(60..310));
+    assert_eq!(ring.merge_over(0..7 * HOUR), expected(60..80));
+    assert_eq!(ring.merge_over(20 * HOUR..=20 * HOUR), expected(200..220));
+    assert_eq!(
+        ring.merge_over(20 * HOUR + 1..21 * HOUR + 1),
+        expected(200..230)
+    );
+    assert_eq!(ring.merge_over(29 * HOUR..), expected(290..310));
+    assert_eq!(ring.merge_over(..0), HyperLogLog::default());
+    assert_eq!(ring.merge_over(40 * HOUR..), HyperLogLog::default());
+    assert_eq!(ring.cardinality_over(..), expected(60..310).cardinality());
+
+    assert_eq!(ring.get(6 * HOUR), Some(&expected(60..80)));
+    assert_eq!(ring.get(5 * HOUR), None);
+    assert_eq!(ring.get(30 * HOUR), None);
+}
+
+#[test]
+fn ring_rotation() {
+    let mut ring = ring(30);
+    assert!(!ring.insert_at(&0, 5 * HOUR));
+    assert!(ring.insert_at(&0, 6 * HOUR));
+
+    ring.rotate_to(40 * HOUR);
+    assert_eq!(ring.merge_over(..), expected(170..310));
+    assert!(ring.get(40 * HOUR).is_some());
+
+    assert!(ring.insert_at(&1000u64, 100 * HOUR));
+    assert_eq!(ring.merge_over(..), expected(1000..1001));
+
+    ring.clear();
+    assert_eq!(ring.merge_over(..), HyperLogLog::default());
+    assert!(ring.insert_hash_at(0, u64::MAX));
+    assert!(ring.get(u64::MAX).is_some());
+}
+
+#[test]
+fn ring_serde() {
+    let ring = ring(30);
+    let json = serde_json::to_string(&ring).unwrap();
+    assert_eq!(
+        serde_json::from_str::<HyperLogLogRing<_>>(&json).unwrap(),
+        ring
+    );
+    let bytes = bincode::serialize(&ring).unwrap();
+    assert_eq!(
+        bincode::deserialize::<HyperLogLogRing<_>>(&bytes).unwrap(),
+        ring
+    );
+
+    assert!(serde_json::from_str::<HyperLogLogRing<[u8; 16]>>("[0,0,[\"\"]]").is_err());
+    assert!(serde_json::from_str::<HyperLogLogRing<[u8; 16]>>("[60,0,[]]").is_err());
+}
@@ -0,0 +1,75 @@
+use hyperloglog::sliding::SlidingHyperLogLog;
+use hyperloglog::HyperLogLog;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+/// `(item, timestamp)` pairs, with repeated items and timestamps.
+fn events() -> Vec<(u32, u64)> {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(47);
+    let mut events: Vec<_> = (0..5000).map(|i| (rng.gen_range(0..2000), i / 3)).collect();
+    events.shuffle(&mut rng);
+    events
+}
+
+fn expected(events: &[(u32, u64)], since: u64) -> HyperLogLog<[u8; 256]> {
+    let mut hll = HyperLogLog::default();
+    for (item, _) in events.iter().filter(|&&(_, t)| t >= since) {
+        hll.insert(item);
+    }
+    hll
+}
+
+#[test]
+fn sliding_matches_hyperloglog() {
+    let events = events();
+    let mut sliding = SlidingHyperLogLog::<[u8; 256]>::default();
+    for &(item, timestamp) in &events {
+        sliding.insert_at(&item, timestamp);
+    }
+    for since in [0, 1, 100, 800, 1666, 1667, 5000] {
+        let expected = expected(&events, since);
+        assert_eq!(sliding.since(since), expected, "{since}");
+        assert_eq!(sliding.cardinality_since(since), expected.cardinality());
+    }
+    assert_eq!(sliding.cardinality_since(u64::MAX), 0);
+}
+
+#[test]
+fn sliding_merge_and_remove() {
+    let events = events();
+    let (a, b) = events.split_at(2000);
+    let mut sliding = SlidingHyperLogLog::<[u8; 256]>::default();
+    let mut other = SlidingHyperLogLog::<[u8; 256]>::default();
+    for &(item, timestamp) in a {
+        sliding.insert_at(&item, timestamp);
+    }
+    for &(item, timestamp) in b {
+        other.insert_at(&item, timestamp);
+    }
+    sliding.merge(&other);
+    for since in [0, 500, 1500] {
+        assert_eq!(sliding.since(since), expected(&events, since));
+    }
+
+    sliding.remove_before(1000);
+    assert_eq!(sliding.since(0), expected(&events, 1000));
+    assert_eq!(sliding.since(1200), expected(&events, 1200));
+
+    sliding.clear();
+    assert_eq!(sliding, SlidingHyperLogLog::default());
+}
+
+#[test]
+fn sliding_window() {
+    let mut sliding = SlidingHyperLogLog::<[u8; 4096]>::default();
+    // 100 distinct items a minute, 50 of them new.
+    for minute in 0..60u64 {
+        for i in 0..100 {
+            sliding.insert_at(&(minute * 50 + i), minute * 60);
+        }
+    }
+    let last_ten = sliding.cardinality_since(50 * 60);
+    assert!((540..=560).contains(&last_ten), "{last_ten}");
+    let all = sliding.cardinality_since(0);
+    assert!((2900..=3150).contains(&all), "{all}");
+}
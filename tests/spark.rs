@@ -0,0 +1,86 @@
+use hyperloglog::spark::{xxhash64, SparkError, XxHash64};
+use hyperloglog::{HyperLogLog, ItemHasher};
+
+/// Precision 4, registers 1, 9, 10 and 15 set to 3, 61, 1 and 2: the words
+/// `61 << 54 | 3 << 6` and `2 << 30 | 1`.
+const BUFFER_4: &[u8] = b"\xc0\x00\x00\x00\x00\x00\x40\x0f\x01\x00\x00\x80\x00\x00\x00\x00";
+
+/// Sets register `item[0]` to `item[1]`.
+struct Direct;
+
+impl ItemHasher for Direct {
+    fn register(&self, item: &[u8], _precision: u8) -> (usize, u8) {
+        (item[0] as usize, item[1])
+    }
+}
+
+#[test]
+fn spark_xxhash64() {
+    assert_eq!(xxhash64(b"", 0), 0xef46db3751d8e999);
+    assert_eq!(xxhash64(b"abc", 0), 0x44bc2cf5ad770999);
+    assert_eq!(xxhash64(b"a", 42), 0x88e4fe59adf7b0cc);
+    assert_eq!(xxhash64(&[7; 40], 42), 0x220358f1ea328f6f);
+    assert_eq!(xxhash64(&1i64.to_le_bytes(), 42), 0x9ed50fd59358d232);
+    assert_eq!(
+        xxhash64(&(0..100).collect::<Vec<u8>>(), 42),
+        0x819d2b726001d507
+    );
+}
+
+#[test]
+fn spark_buffer() {
+    let hll = HyperLogLog::<[u8; 16]>::from_spark(BUFFER_4).unwrap();
+    let mut expected = HyperLogLog::<[u8; 16]>::default();
+    for (index, value) in [(1, 3), (9, 61), (10, 1), (15, 2)] {
+        expected.insert_with(&Direct, &[index, value]);
+    }
+    assert_eq!(hll, expected);
+    assert_eq!(hll.to_spark(), BUFFER_4);
+}
+
+#[test]
+fn spark_round_trip() {
+    let mut hll = HyperLogLog::<[u8; 512]>::default();
+    let empty = hll.to_spark();
+    assert_eq!(empty, [0; 52 * 8]);
+    assert_eq!(HyperLogLog::from_spark(&empty), Ok(hll.clone()));
+
+    for i in 0..10000i64 {
+        hll.insert_with(&XxHash64, &i.to_le_bytes());
+    }
+    assert_eq!(HyperLogLog::from_spark(&hll.to_spark()), Ok(hll.clone()));
+    let error = (hll.cardinality() as f64 - 10000.0).abs() / 10000.0;
+    assert!(error < 0.15, "{error}");
+}
+
+#[test]
+fn spark_fold() {
+    let mut large = HyperLogLog::<[u8; 4096]>::default();
+    let mut small = HyperLogLog::<[u8; 512]>::default();
+    for i in 0..5000i64 {
+        large.insert_with(&XxHash64, &i.to_le_bytes());
+        small.insert_with(&XxHash64, &i.to_le_bytes());
+    }
+    let bytes = large.to_spark();
+    assert_eq!(HyperLogLog::from_spark(&bytes), Ok(small));
+    assert_eq!(
+        HyperLogLog::<[u8; 8192]>::from_spark(&bytes),
+        Err(SparkError::PrecisionTooLow(12))
+    );
+}
+
+#[test]
+fn spark_invalid() {
+    type Hll = HyperLogLog<[u8; 16]>;
+    assert_eq!(Hll::from_spark(b""), Err(SparkError::InvalidLength));
+    assert_eq!(
+        Hll::from_spark(&BUFFER_4[..15]),
+        Err(SparkError::InvalidLength)
+    );
+    assert_eq!(Hll::from_spark(&[0; 24]), Err(SparkError::InvalidLength));
+
+    // Register 9 past the largest value at precision 4.
+    let mut bytes = BUFFER_4.to_vec();
+    bytes[6] = 0x80;
+    assert_eq!(Hll::from_spark(&bytes), Err(SparkError::InvalidRegisters));
+}
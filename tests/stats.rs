@@ -0,0 +1,58 @@
+use hyperloglog::{Estimator, HyperLogLog, HyperLogLogRef, Registers};
+
+fn stats<R: Registers>(count: u64) -> (HyperLogLog<R>, hyperloglog::Stats) {
+    let mut hll = HyperLogLog::<R>::default();
+    for i in 0..count {
+        hll.insert(&i);
+    }
+    let stats = hll.stats();
+    assert_eq!(stats.histogram.len(), R::MAX_VALUE as usize + 1);
+    assert_eq!(stats.histogram.iter().sum::<usize>(), R::REGISTERS);
+    assert_eq!(stats.zero_registers, stats.histogram[0]);
+    assert_eq!(stats.estimate.round() as u64, hll.cardinality());
+    (hll, stats)
+}
+
+#[test]
+fn stats_empty() {
+    let (_, stats) = stats::<[u8; 1024]>(0);
+    assert_eq!(stats.zero_registers, 1024);
+    assert_eq!(stats.estimator, Estimator::LinearCounting);
+    assert_eq!(stats.estimate, 0.0);
+    assert_eq!(stats.bias, 0.0);
+}
+
+#[test]
+fn stats_estimators() {
+    let (_, linear) = stats::<[u8; 16]>(5);
+    assert_eq!(linear.estimator, Estimator::LinearCounting);
+    assert_eq!(linear.bias, 0.0);
+
+    let (_, corrected) = stats::<[u8; 16]>(40);
+    assert_eq!(corrected.estimator, Estimator::BiasCorrected);
+    assert_ne!(corrected.bias, 0.0);
+    assert_eq!(corrected.estimate, corrected.raw_estimate - corrected.bias);
+
+    let (hll, raw) = stats::<[u8; 16]>(10000);
+    assert_eq!(raw.estimator, Estimator::Raw);
+    assert_eq!(raw.zero_registers, 0);
+    assert_eq!(raw.bias, 0.0);
+    assert_eq!(raw.estimate, raw.raw_estimate);
+
+    let registers: [u8; 16] = hll.registers().try_into().unwrap();
+    let borrowed = HyperLogLogRef::<[u8; 16]>::new(&registers).unwrap();
+    assert_eq!(borrowed.stats(), raw);
+}
+
+#[test]
+fn stats_histogram() {
+    let mut hll = HyperLogLog::<[u8; 16]>::default();
+    for hash in [0, 1, 0x10, u64::MAX, 0x8000_0000_0000_0003] {
+        hll.insert_hash(hash);
+    }
+    let mut expected = vec![0; 62];
+    expected[0] = 12;
+    expected[1] = 2;
+    expected[61] = 2;
+    assert_eq!(hll.stats().histogram, expected);
+}
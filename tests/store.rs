@@ -0,0 +1,176 @@
+use hyperloglog::store::{self, Store, StoreError};
+use hyperloglog::HyperLogLog;
+use std::ops::Bound;
+use std::path::PathBuf;
+
+type R = [u8; 256];
+
+/// A path in the temporary directory, removed when dropped.
+struct TempPath(PathBuf);
+
+impl TempPath {
+    fn new(name: &str) -> Self {
+        let name = format!("hyperloglog-{}-{name}", std::process::id());
+        Self(std::env::temp_dir().join(name))
+    }
+}
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+#[test]
+fn store_create_and_open() {
+    let path = TempPath::new("create");
+    let mut store = Store::<R>::create(&path.0, [30, 10, 20, 10]).unwrap();
+    assert_eq!(store.len(), 3);
+    assert_eq!(store.keys().collect::<Vec<_>>(), [10, 20, 30]);
+    assert_eq!(store.get(20).unwrap(), HyperLogLog::<R>::default());
+    assert!(store.get(15).is_none());
+
+    let mut expected = HyperLogLog::<R>::default();
+    for i in 0..100u32 {
+        assert!(store.insert(20, &i));
+        expected.insert(&i);
+    }
+    assert!(!store.insert(15, &0u32));
+    assert_eq!(store.get(20).unwrap(), expected);
+    store.flush().unwrap();
+    drop(store);
+
+    let len = std::fs::metadata(&path.0).unwrap().len();
+    assert_eq!(len, 16 + 3 * (8 + 256));
+    let store = Store::<R>::open(&path.0).unwrap();
+    assert_eq!(store.keys().collect::<Vec<_>>(), [10, 20, 30]);
+    assert_eq!(store.get(20).unwrap(), expected);
+    assert_eq!(store.get(10).unwrap().cardinality(), 0);
+
+    assert!(matches!(
+        Store::<[u8; 512]>::open(&path.0),
+        Err(StoreError::PrecisionMismatch(8))
+    ));
+}
+
+#[test]
+fn store_add_key() {
+    let path = TempPath::new("add");
+    let mut store = Store::<R>::create(&path.0, []).unwrap();
+    assert!(store.is_empty());
+    let mut sketches = Vec::new();
+    for name in ["fr", "de", "us", "jp"] {
+        assert!(store.add_key(store::key(name)).unwrap());
+        let mut hll = HyperLogLog::<R>::default();
+        for i in 0..name.len() as u32 * 100 {
+            assert!(store.insert(store::key(name), &(name, i)));
+            hll.insert(&(name, i));
+        }
+        sketches.push((name, hll));
+    }
+    assert!(!store.add_key(store::key("de")).unwrap());
+    assert_eq!(store.len(), 4);
+    for (name, hll) in &sketches {
+        assert_eq!(store.get(store::key(name)).unwrap(), *hll);
+    }
+    store.flush().unwrap();
+    drop(store);
+
+    let store = Store::<R>::open(&path.0).unwrap();
+    let mut keys = sketches
+        .iter()
+        .map(|(name, _)| store::key(name))
+        .collect::<Vec<_>>();
+    keys.sort_unstable();
+    assert_eq!(store.keys().collect::<Vec<_>>(), keys);
+    for (name, hll) in &sketches {
+        assert_eq!(store.get(store::key(name)).unwrap(), *hll);
+    }
+}
+
+#[test]
+fn store_large_precision() {
+    let path = TempPath::new("large");
+    let mut store = Store::<[u8; 16384]>::create(&path.0, [1, 2]).unwrap();
+    let mut expected = HyperLogLog::<[u8; 16384]>::default();
+    for i in 0..100000u32 {
+        assert!(store.insert(2, &i));
+        expected.insert(&i);
+    }
+    assert_eq!(store.get(2).unwrap(), expected);
+    assert_eq!(store.get(1).unwrap().cardinality(), 0);
+}
+
+#[test]
+fn store_merge_range() {
+    let path = TempPath::new("merge");
+    let mut store = Store::<R>::create(&path.0, 0..10).unwrap();
+    let mut sketches = Vec::new();
+    for key in 0..10u64 {
+        let mut hll = HyperLogLog::<R>::default();
+        for i in 0..50 * key {
+            hll.insert(&(key, i));
+        }
+        assert!(store.merge(key, &hll));
+        sketches.push(hll);
+    }
+    assert!(!store.merge(10, &sketches[0]));
+
+    let merged = |keys: std::ops::Range<usize>| {
+        let mut ret = HyperLogLog::<R>::default();
+        for hll in &sketches[keys] {
+            ret.merge(hll);
+        }
+        ret
+    };
+    assert_eq!(store.merge_range(2..5), merged(2..5));
+    assert_eq!(store.merge_range(2..=5), merged(2..6));
+    assert_eq!(store.merge_range(..3), merged(0..3));
+    assert_eq!(store.merge_range(7..), merged(7..10));
+    assert_eq!(store.merge_range(..), merged(0..10));
+    assert_eq!(store.merge_range(20..30), HyperLogLog::default());
+    assert_eq!(
+        store.merge_range((Bound::Excluded(5), Bound::Excluded(5))),
+        HyperLogLog::default()
+    );
+}
+
+#[test]
+fn store_invalid() {
+    let path = TempPath::new("invalid");
+    drop(Store::<R>::create(&path.0, [1, 2]).unwrap());
+    let valid = std::fs::read(&path.0).unwrap();
+
+    let open = |bytes: &[u8]| {
+        std::fs::write(&path.0, bytes).unwrap();
+        Store::<R>::open(&path.0)
+    };
+    assert!(open(&valid).is_ok());
+    assert!(matches!(open(&valid[..10]), Err(StoreError::InvalidHeader)));
+    assert!(matches!(
+        open(&valid[..valid.len() - 1]),
+        Err(StoreError::InvalidLength)
+    ));
+
+    let mut bytes = valid.clone();
+    bytes[0] = b'X';
+    assert!(matches!(open(&bytes), Err(StoreError::InvalidHeader)));
+
+    // Swap the keys.
+    let mut bytes = valid.clone();
+    bytes[16] = 2;
+    bytes[24] = 1;
+    assert!(matches!(open(&bytes), Err(StoreError::UnsortedKeys)));
+
+    // Invalid registers are skipped rather than borrowed.
+    let mut bytes = valid.clone();
+    bytes[32] = 255;
+    let store = open(&bytes).unwrap();
+    assert!(store.get(1).is_none());
+    assert_eq!(store.merge_range(..), HyperLogLog::default());
+
+    assert!(matches!(
+        Store::<R>::open(path.0.with_extension("missing")),
+        Err(StoreError::Io(_))
+    ));
+}
@@ -0,0 +1,117 @@
+use hyperloglog::streamlib::{murmurhash64a, MurmurHash64A, StreamLibError};
+use hyperloglog::{HyperLogLog, ItemHasher};
+
+/// Normal format with precision 4, registers 0, 5, 6 and 15 set to 1, 31, 2
+/// and 7: the words `31 << 25 | 1`, `2` and `7 << 15`.
+const NORMAL_4: &[u8] =
+    b"\xff\xff\xff\xfe\x04\x00\x00\x0c\x3e\x00\x00\x01\x00\x00\x00\x02\x00\x03\x80\x00";
+
+/// Sparse format with precision 4 and sparse precision 10, and three values
+/// in order of sparse index:
+/// - `0001 000000`, with a value of 1 past it, `0001000000 000001 1`.
+/// - `0010 000100`, `0010000100 0`.
+/// - `0101 000000`, with a value of 3 past it, `0101000000 000011 1`.
+///
+/// They are difference encoded as the varints 8195, -7931 and 40703.
+const SPARSE_4_10: &[u8] =
+    b"\xff\xff\xff\xfe\x04\x0a\x01\x03\x83\x40\x85\xc2\xff\xff\x0f\xff\xbd\x02";
+
+/// Sets register `item[0]` to `item[1]`.
+struct Direct;
+
+impl ItemHasher for Direct {
+    fn register(&self, item: &[u8], _precision: u8) -> (usize, u8) {
+        (item[0] as usize, item[1])
+    }
+}
+
+fn with_registers(registers: &[(u8, u8)]) -> HyperLogLog<[u8; 16]> {
+    let mut hll = HyperLogLog::default();
+    for &(index, value) in registers {
+        hll.insert_with(&Direct, &[index, value]);
+    }
+    hll
+}
+
+#[test]
+fn streamlib_murmurhash64a() {
+    // From Austin Appleby's reference implementation.
+    assert_eq!(murmurhash64a(b"", 0xe17a1465), 0x9bfae0a4e613fc3c);
+    assert_eq!(murmurhash64a(b"hello", 0xe17a1465), 0xc656272f1aa32ab5);
+    assert_eq!(
+        murmurhash64a(b"stream-lib hyperloglog", 0xe17a1465),
+        0x3966efa1e22417e5
+    );
+}
+
+#[test]
+fn streamlib_normal() {
+    let hll = HyperLogLog::<[u8; 16]>::from_streamlib(NORMAL_4).unwrap();
+    assert_eq!(hll, with_registers(&[(0, 1), (5, 31), (6, 2), (15, 7)]));
+    assert_eq!(hll.to_streamlib(), NORMAL_4);
+
+    // Values past 31 are saturated.
+    let hll = with_registers(&[(0, 1), (5, 40), (6, 2), (15, 7)]);
+    assert_eq!(hll.to_streamlib(), NORMAL_4);
+}
+
+#[test]
+fn streamlib_sparse() {
+    let hll = HyperLogLog::<[u8; 16]>::from_streamlib(SPARSE_4_10).unwrap();
+    assert_eq!(hll, with_registers(&[(1, 7), (2, 4), (5, 9)]));
+}
+
+#[test]
+fn streamlib_round_trip() {
+    let mut hll = HyperLogLog::<[u8; 2048]>::default();
+    for i in 0..10000u32 {
+        hll.insert_with(&MurmurHash64A, i.to_string().as_bytes());
+    }
+    assert_eq!(
+        HyperLogLog::from_streamlib(&hll.to_streamlib()),
+        Ok(hll.clone())
+    );
+    let error = (hll.cardinality() as f64 - 10000.0).abs() / 10000.0;
+    assert!(error < 0.1, "{error}");
+}
+
+#[test]
+fn streamlib_fold() {
+    let mut large = HyperLogLog::<[u8; 4096]>::default();
+    let mut small = HyperLogLog::<[u8; 1024]>::default();
+    for i in 0..5000u32 {
+        large.insert_with(&MurmurHash64A, &i.to_le_bytes());
+        small.insert_with(&MurmurHash64A, &i.to_le_bytes());
+    }
+    let bytes = large.to_streamlib();
+    assert_eq!(HyperLogLog::from_streamlib(&bytes), Ok(small));
+    assert_eq!(
+        HyperLogLog::<[u8; 8192]>::from_streamlib(&bytes),
+        Err(StreamLibError::PrecisionTooLow(12))
+    );
+}
+
+#[test]
+fn streamlib_invalid() {
+    type Hll = HyperLogLog<[u8; 16]>;
+    assert_eq!(Hll::from_streamlib(b""), Err(StreamLibError::InvalidHeader));
+    // The original serialization, without a version.
+    assert_eq!(
+        Hll::from_streamlib(b"\x00\x00\x00\x04\x00\x00\x00\x0c"),
+        Err(StreamLibError::InvalidHeader)
+    );
+    assert_eq!(
+        Hll::from_streamlib(&NORMAL_4[..NORMAL_4.len() - 1]),
+        Err(StreamLibError::InvalidLength)
+    );
+    assert_eq!(
+        Hll::from_streamlib(&SPARSE_4_10[..SPARSE_4_10.len() - 1]),
+        Err(StreamLibError::InvalidLength)
+    );
+
+    // A sparse index whose extra bits are all zero, `0001000000 0`.
+    assert_eq!(
+        Hll::from_streamlib(b"\xff\xff\xff\xfe\x04\x0a\x01\x01\x80\x01"),
+        Err(StreamLibError::InvalidRegisters)
+    );
+}
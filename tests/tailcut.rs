@@ -0,0 +1,129 @@
+use hyperloglog::tailcut::TailCut;
+use hyperloglog::{HyperLogLog, Registers};
+
+/// The largest relative errors of `TailCut<R>` and `HyperLogLog<R>` of the
+/// same items, like `test_precision` in `tests.rs`, up to `max_count` items.
+fn test_precision<R: Registers>(samples: usize, max_count: usize) -> (f64, f64) {
+    let mut tailcut = TailCut::<R>::default();
+    let mut hll = HyperLogLog::<R>::default();
+    let mut count = 1;
+    let mut max_error = f64::NEG_INFINITY;
+    let mut max_hll_error = f64::NEG_INFINITY;
+    while count <= max_count {
+        for _ in 0..samples {
+            tailcut.clear();
+            hll.clear();
+            for _ in 0..count {
+                let item = rand::random::<u128>();
+                tailcut.insert(&item);
+                hll.insert(&item);
+            }
+            let error = |estimate: u64| (estimate as f64 - count as f64).abs() / count as f64;
+            max_error = max_error.max(error(tailcut.cardinality()));
+            max_hll_error = max_hll_error.max(error(hll.cardinality()));
+        }
+        count *= 10;
+    }
+    println!(
+        "with {}, {max_error:.3} in {} bytes, {max_hll_error:.3} in {}",
+        R::PRECISION,
+        R::REGISTERS / 2,
+        R::REGISTERS
+    );
+    (max_error, max_hll_error)
+}
+
+#[test]
+fn tailcut_precisions() {
+    test_precision::<[u8; 16]>(16, 100000);
+    test_precision::<[u8; 64]>(16, 100000);
+    test_precision::<[u8; 256]>(16, 100000);
+    test_precision::<[u8; 1024]>(16, 100000);
+    let (error, hll_error) = test_precision::<[u8; 2048]>(16, 100000);
+    assert!(error < 0.25);
+    assert!(error < hll_error + 0.01, "{error} {hll_error}");
+}
+
+#[test]
+fn tailcut_large_precisions() {
+    test_precision::<[u8; 4096]>(1, 1000000);
+    test_precision::<[u8; 16384]>(1, 1000000);
+    let (error, hll_error) = test_precision::<[u8; 65536]>(1, 1000000);
+    assert!(error < 0.05);
+    assert!(error < hll_error + 0.01, "{error} {hll_error}");
+}
+
+#[test]
+fn tailcut_matches_hyperloglog() {
+    let mut tailcut = TailCut::<[u8; 1024]>::default();
+    let mut hll = HyperLogLog::<[u8; 1024]>::default();
+    for i in 0..100000u32 {
+        tailcut.insert(&i);
+        hll.insert(&i);
+        if i % 10000 == 0 {
+            // The registers are the same, except those cut to 15 above the
+            // base when they were set, which is at most the smallest now.
+            let cut = tailcut.to_hyperloglog();
+            for (&cut, &register) in cut.registers().iter().zip(hll.registers()) {
+                assert!(cut == register || (15..register).contains(&cut));
+            }
+        }
+    }
+    let error = tailcut.cardinality().abs_diff(hll.cardinality()) as f64 / 100000.0;
+    assert!(error < 0.001, "{error}");
+}
+
+/// A hash counted as `value` in register `index` of 16.
+fn hash(index: u64, value: u8) -> u64 {
+    1 << (64 - value) | index
+}
+
+#[test]
+fn tailcut_cut() {
+    let mut tailcut = TailCut::<[u8; 16]>::default();
+    let mut expected = HyperLogLog::<[u8; 16]>::default();
+    // Register 0 is still 0, so the base is 0, and the rest are cut to 15.
+    for index in 1..16 {
+        tailcut.insert_hash(hash(index, 20));
+        expected.insert_hash(hash(index, 15));
+    }
+    assert_eq!(tailcut.to_hyperloglog(), expected);
+
+    // Once register 0 is 1, so is the base, and they're cut to 16.
+    tailcut.insert_hash(hash(0, 1));
+    expected.insert_hash(hash(0, 1));
+    assert_eq!(tailcut.to_hyperloglog(), expected);
+    tailcut.insert_hash(hash(1, 20));
+    expected.insert_hash(hash(1, 16));
+    assert_eq!(tailcut.to_hyperloglog(), expected);
+    assert_eq!(TailCut::from(&expected), tailcut);
+}
+
+#[test]
+fn tailcut_merge() {
+    for count in [500, 100000] {
+        let (mut a, mut b) = (TailCut::<[u8; 256]>::default(), TailCut::default());
+        let mut hll = HyperLogLog::<[u8; 256]>::default();
+        for i in 0..count {
+            if i % 3 == 0 {
+                a.insert(&i);
+            } else {
+                b.insert(&i);
+            }
+            hll.insert(&i);
+        }
+        a.merge(&b);
+        if count == 500 {
+            // None are cut while there are zero registers.
+            assert_eq!(a, TailCut::from(&hll));
+        }
+        let error = a.cardinality().abs_diff(hll.cardinality()) as f64 / count as f64;
+        assert!(error < 0.01, "{error}");
+
+        let merged = a.clone();
+        a.merge(&TailCut::default());
+        assert_eq!(a, merged);
+        a.clear();
+        assert_eq!(a, TailCut::default());
+    }
+}
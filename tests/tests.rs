@@ -1,4 +1,4 @@
-use hyperloglog::{HyperLogLog, Registers};
+use hyperloglog::{DecompressError, HyperLogLog, Registers};
 
 fn test_precision<R: Registers>() -> f64 {
     let mut hll = HyperLogLog::<R>::default();
@@ -57,6 +57,40 @@ fn test_precisions() {
     assert!(test_precision::<[u8; 2048]>() < 0.25);
 }
 
+fn test_large_precision<R: Registers>() -> f64 {
+    let mut hll = HyperLogLog::<R>::default();
+    let count = 1000000;
+    for _ in 0..count {
+        hll.insert(&rand::random::<u128>());
+    }
+    let compressed = bincode::serialize(&hll).unwrap();
+    let decompressed = bincode::deserialize::<HyperLogLog<R>>(&compressed).unwrap();
+    assert!(hll == decompressed);
+    let error = (hll.cardinality() as f64 - count as f64).abs() / count as f64;
+    println!("with {}, {error:.3}", R::PRECISION);
+    error
+}
+
+#[test]
+fn test_large_precisions() {
+    // Unoptimized builds copy the registers around on the stack, which
+    // overflows a test thread's for the largest precisions.
+    std::thread::Builder::new()
+        .stack_size(64 << 20)
+        .spawn(|| {
+            test_large_precision::<[u8; 4096]>();
+            test_large_precision::<[u8; 8192]>();
+            test_large_precision::<[u8; 16384]>();
+            test_large_precision::<[u8; 32768]>();
+            test_large_precision::<[u8; 65536]>();
+            test_large_precision::<[u8; 131072]>();
+            assert!(test_large_precision::<[u8; 262144]>() < 0.05);
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
 #[test]
 fn hyperloglog_test_simple() {
     let mut hll = HyperLogLog::<[u8; 64]>::default();
@@ -88,3 +122,123 @@ fn hyperloglog_test_merge() {
     hll.merge(&hll2);
     assert_eq!(hll.cardinality(), 4);
 }
+
+#[test]
+fn hyperloglog_test_sparse_compression() {
+    let mut hll = HyperLogLog::<[u8; 2048]>::default();
+    let empty = bincode::serialize(&hll).unwrap();
+    assert!(empty.len() - 8 <= 2, "{}", empty.len());
+
+    for i in 0..10u32 {
+        hll.insert(&i);
+    }
+    let compressed = bincode::serialize(&hll).unwrap();
+    assert!(compressed.len() - 8 <= 2 + 10 * 3, "{}", compressed.len());
+    let decompressed = bincode::deserialize::<HyperLogLog<[u8; 2048]>>(&compressed).unwrap();
+    assert!(hll == decompressed);
+
+    for i in 10..100000u32 {
+        hll.insert(&i);
+    }
+    let compressed = bincode::serialize(&hll).unwrap();
+    let decompressed = bincode::deserialize::<HyperLogLog<[u8; 2048]>>(&compressed).unwrap();
+    assert!(hll == decompressed);
+}
+
+#[test]
+fn hyperloglog_test_untagged_compression() {
+    // Serialized before the sparse encoding was added.
+    type Fixture = (
+        HyperLogLog<[u8; 16]>,
+        HyperLogLog<[u8; 16]>,
+        HyperLogLog<[u8; 64]>,
+        HyperLogLog<[u8; 2048]>,
+    );
+    let (empty, three, hundred, ten_thousand) =
+        serde_json::from_str::<Fixture>(include_str!("fixtures/baseline.json")).unwrap();
+    assert!(empty == HyperLogLog::default());
+    let mut hll = HyperLogLog::<[u8; 16]>::default();
+    for i in 0..3u32 {
+        hll.insert(&i);
+    }
+    assert!(three == hll);
+    let mut hll = HyperLogLog::<[u8; 64]>::default();
+    for i in 0..100u32 {
+        hll.insert(&i);
+    }
+    assert!(hundred == hll);
+    let mut hll = HyperLogLog::<[u8; 2048]>::default();
+    for i in 0..10000u32 {
+        hll.insert(&i);
+    }
+    assert!(ten_thousand == hll);
+}
+
+#[test]
+fn hyperloglog_test_max_value_compression() {
+    fn round_trip(registers: [u8; 16]) {
+        let compressed = registers.compress();
+        let mut decompressed = [0u8; 16];
+        decompressed.decompress(&compressed).unwrap();
+        assert_eq!(registers, decompressed);
+    }
+
+    const MAX: u8 = <[u8; 16] as Registers>::MAX_VALUE;
+    round_trip([MAX; 16]);
+    round_trip([MAX - 1; 16]);
+    let mut registers = [1; 16];
+    registers[0] = MAX - 1;
+    round_trip(registers);
+    registers[5] = MAX;
+    round_trip(registers);
+    round_trip([0, 0, 0, MAX, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+}
+
+#[test]
+fn hyperloglog_test_streaming_compression() {
+    let mut hll = HyperLogLog::<[u8; 2048]>::default();
+    let mut inserted = 0u32;
+    for count in [0u32, 10, 100000] {
+        for i in inserted..count {
+            hll.insert(&i);
+        }
+        inserted = count;
+
+        let bincode = bincode::serialize(&hll).unwrap();
+        let mut appended = vec![1, 2, 3];
+        hll.compress_into(&mut appended);
+        assert_eq!(appended[..3], [1, 2, 3]);
+        assert_eq!(appended[3..], bincode[8..]);
+        let mut written = Vec::new();
+        hll.write_compressed(&mut written).unwrap();
+        assert_eq!(written, bincode[8..]);
+
+        let json = serde_json::to_string(&hll).unwrap();
+        let mut base64 = String::from("\"");
+        hll.write_base64(&mut base64).unwrap();
+        base64.push('"');
+        assert_eq!(base64, json);
+        assert!(HyperLogLog::from_base64(&json[1..json.len() - 1]).unwrap() == hll);
+    }
+}
+
+#[test]
+fn hyperloglog_test_invalid_base64() {
+    let mut hll = HyperLogLog::<[u8; 64]>::default();
+    for i in 0..100u32 {
+        hll.insert(&i);
+    }
+    let mut base64 = String::new();
+    hll.write_base64(&mut base64).unwrap();
+    // Past the end of what the dense decoder reads.
+    base64.push_str("A!");
+    assert_eq!(
+        HyperLogLog::<[u8; 64]>::from_base64(&base64),
+        Err(DecompressError::InvalidBase64)
+    );
+    assert!(serde_json::from_str::<HyperLogLog<[u8; 64]>>(&format!("\"{base64}\"")).is_err());
+    assert_eq!(
+        HyperLogLog::<[u8; 64]>::from_base64("/wEA!"),
+        Err(DecompressError::InvalidBase64)
+    );
+}
@@ -0,0 +1,114 @@
+use hyperloglog::text::{base64_url, hex, z85};
+use hyperloglog::HyperLogLog;
+
+type Hll = HyperLogLog<[u8; 64]>;
+
+fn hll(count: u32) -> Hll {
+    let mut hll = Hll::default();
+    for i in 0..count {
+        hll.insert(&i);
+    }
+    hll
+}
+
+macro_rules! to_json {
+    ($hll:expr, $module:ident) => {{
+        let mut json = Vec::new();
+        $module::serialize($hll, &mut serde_json::Serializer::new(&mut json)).unwrap();
+        String::from_utf8(json).unwrap()
+    }};
+}
+
+macro_rules! from_json {
+    ($json:expr, $module:ident) => {
+        $module::deserialize::<[u8; 64], _>(&mut serde_json::Deserializer::from_str($json))
+    };
+}
+
+/// The bytes the default serialization encodes.
+fn compressed(hll: &Hll) -> Vec<u8> {
+    let mut compressed = Vec::new();
+    hll.compress_into(&mut compressed);
+    compressed
+}
+
+#[test]
+fn text_hex() {
+    // Sparse: the tags, then varint `(index delta << 6) | value` pairs.
+    let hll = hll(1);
+    let compressed = compressed(&hll);
+    assert_eq!(compressed[..2], [0xff, 0x01]);
+    let expected = compressed
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect::<String>();
+    let json = to_json!(&hll, hex);
+    assert_eq!(json, format!("\"{expected}\""));
+    assert!(from_json!(&json, hex).unwrap() == hll);
+    assert!(from_json!(&json.to_uppercase(), hex).unwrap() == hll);
+
+    for count in [0, 100, 10000] {
+        let hll = self::hll(count);
+        let json = to_json!(&hll, hex);
+        assert!(from_json!(&json, hex).unwrap() == hll);
+    }
+
+    assert!(from_json!("\"ff0\"", hex).is_err());
+    assert!(from_json!("\"ff0g\"", hex).is_err());
+}
+
+#[test]
+fn text_z85() {
+    // Sparse, registers 0, 1 and 2 set to 1, 2 and 3: `ff 01 01 02 03`,
+    // whose last group is the byte `03` padded to `03 00 00 00`, or
+    // `0@@r3` of which `0@` is kept.
+    let hll = from_json!("\"ff01010203\"", hex).unwrap();
+    assert!(from_json!("\"@@Ac40@\"", z85).unwrap() == hll);
+    assert!(from_json!("\"@@Ac40@\"", z85).unwrap() != Hll::default());
+
+    for count in [0, 1, 2, 3, 100, 10000] {
+        let hll = self::hll(count);
+        let json = to_json!(&hll, z85);
+        // A final group of n bytes is n + 1 characters.
+        let len = compressed(&hll).len();
+        let tail = if len % 4 == 0 { 0 } else { len % 4 + 1 };
+        assert_eq!(json.len() - 2, len / 4 * 5 + tail);
+        assert!(from_json!(&json, z85).unwrap() == hll);
+    }
+
+    assert!(from_json!("\"~~~~~\"", z85).is_err());
+    // Past `u32::MAX`.
+    assert!(from_json!("\"#####\"", z85).is_err());
+    assert!(from_json!("\"@@Ac40\"", z85).is_err());
+}
+
+#[test]
+fn text_base64_url() {
+    for count in [0, 1, 100, 10000] {
+        let hll = self::hll(count);
+        let json = to_json!(&hll, base64_url);
+        assert!(!json.contains(['+', '/', '=']));
+        assert!(from_json!(&json, base64_url).unwrap() == hll);
+    }
+    // Standard base64 of the sparse tags.
+    assert!(from_json!("\"/wE\"", base64_url).is_err());
+    assert!(from_json!("\"_wE\"", base64_url).unwrap() == Hll::default());
+}
+
+#[test]
+fn text_binary() {
+    // Serializers that aren't human-readable get the bytes as is.
+    let hll = hll(100);
+    let mut bytes = Vec::new();
+    z85::serialize(
+        &hll,
+        &mut bincode::Serializer::new(&mut bytes, bincode::options()),
+    )
+    .unwrap();
+    let mut deserializer = bincode::Deserializer::from_slice(&bytes, bincode::options());
+    assert!(z85::deserialize::<[u8; 64], _>(&mut deserializer).unwrap() == hll);
+    assert_eq!(
+        bytes,
+        bincode::Options::serialize(bincode::options(), &hll).unwrap()
+    );
+}
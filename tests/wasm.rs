@@ -0,0 +1,64 @@
+//! Run in a headless browser with `make test-wasm`, which needs
+//! `wasm-bindgen-cli` and a WebDriver such as `geckodriver` installed.
+
+#![cfg(target_arch = "wasm32")]
+
+use hyperloglog::wasm::JsHyperLogLog;
+use hyperloglog::HyperLogLog;
+use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+#[wasm_bindgen_test]
+fn wasm_matches_serde() {
+    let mut js = JsHyperLogLog::new(14).unwrap();
+    let mut hll = HyperLogLog::<[u8; 16384]>::default();
+    for i in 0..1000 {
+        let item = format!("/items/{i}");
+        js.insert(&item);
+        hll.insert(&item.as_str());
+        js.insert_bytes(&[i as u8, 1]);
+        hll.insert(&[i as u8, 1].as_slice());
+    }
+    assert_eq!(js.precision(), 14);
+    assert_eq!(js.cardinality(), hll.cardinality() as f64);
+
+    // What the backend receives.
+    let base64 = js.to_base64();
+    assert_eq!(
+        serde_json::to_string(&hll).unwrap(),
+        format!("\"{base64}\"")
+    );
+    let decoded: HyperLogLog<[u8; 16384]> = serde_json::from_str(&format!("\"{base64}\"")).unwrap();
+    assert_eq!(decoded, hll);
+
+    let round_trip = JsHyperLogLog::from_base64(14, &base64).unwrap();
+    assert_eq!(round_trip.to_base64(), base64);
+}
+
+#[wasm_bindgen_test]
+fn wasm_merge() {
+    let mut a = JsHyperLogLog::new(10).unwrap();
+    let mut b = JsHyperLogLog::new(10).unwrap();
+    let mut hll = HyperLogLog::<[u8; 1024]>::default();
+    for i in 0..100 {
+        a.insert(&i.to_string());
+        b.insert(&(i + 50).to_string());
+    }
+    for i in 0..150 {
+        hll.insert(&i.to_string().as_str());
+    }
+    a.merge(&b).unwrap();
+    assert_eq!(a.cardinality(), hll.cardinality() as f64);
+
+    a.clear();
+    assert_eq!(a.cardinality(), 0.0);
+    assert!(a.merge(&JsHyperLogLog::new(11).unwrap()).is_err());
+}
+
+#[wasm_bindgen_test]
+fn wasm_invalid() {
+    assert!(JsHyperLogLog::new(3).is_err());
+    assert!(JsHyperLogLog::new(19).is_err());
+    assert!(JsHyperLogLog::from_base64(14, "not base64!").is_err());
+}
@@ -0,0 +1,124 @@
+use hyperloglog::zetasketch::{fingerprint2011, value_type, Fingerprint2011, ZetaSketchError};
+use hyperloglog::{HyperLogLog, ItemHasher};
+
+/// Precision 10 and sparse precision 15, with value type 4 and two sparse
+/// values: sparse index `00000 00011 00100`, and normal index 5 with a
+/// value of 2 past the sparse index, `1 0000000101 000010`. They are
+/// difference encoded as the varints 100 and 65758.
+const SPARSE: &[u8] =
+    b"\x08\x70\x18\x02\x20\x04\x82\x07\x0c\x10\x02\x18\x0a\x20\x0f\x32\x04\x64\xde\x81\x04";
+
+/// Sets register `item[0]` to `item[1]`.
+struct Direct;
+
+impl ItemHasher for Direct {
+    fn register(&self, item: &[u8], _precision: u8) -> (usize, u8) {
+        (item[0] as usize, item[1])
+    }
+}
+
+#[test]
+fn zetasketch_fingerprint2011() {
+    // From Guava's `Fingerprint2011Test`, covering inputs of up to 32 bytes
+    // and over 64 bytes.
+    assert_eq!(fingerprint2011(b"test") as i64, 8473225671271759044);
+    assert_eq!(
+        fingerprint2011("test".repeat(8).as_bytes()) as i64,
+        7345148637025587076
+    );
+    assert_eq!(
+        fingerprint2011("test".repeat(64).as_bytes()) as i64,
+        4904844928629814570
+    );
+}
+
+#[test]
+fn zetasketch_sparse() {
+    let hll = HyperLogLog::<[u8; 1024]>::from_zetasketch(SPARSE).unwrap();
+    let mut expected = HyperLogLog::<[u8; 1024]>::default();
+    expected.insert_with(&Direct, &[3, 3]);
+    expected.insert_with(&Direct, &[5, 7]);
+    assert_eq!(hll, expected);
+    assert_eq!(value_type(SPARSE), Ok(Some(4)));
+
+    // Folded to precision 9, the dropped index bits are both 1.
+    let hll = HyperLogLog::<[u8; 512]>::from_zetasketch(SPARSE).unwrap();
+    let mut expected = HyperLogLog::<[u8; 512]>::default();
+    expected.insert_with(&Direct, &[1, 1]);
+    expected.insert_with(&Direct, &[2, 1]);
+    assert_eq!(hll, expected);
+}
+
+#[test]
+fn zetasketch_round_trip() {
+    let mut hll = HyperLogLog::<[u8; 1024]>::default();
+    let empty = hll.to_zetasketch(None);
+    assert_eq!(&empty[..8], b"\x08\x70\x18\x02\x82\x07\x89\x08");
+    assert_eq!(value_type(&empty), Ok(None));
+    assert_eq!(HyperLogLog::from_zetasketch(&empty), Ok(hll.clone()));
+
+    for i in 0..10000u32 {
+        hll.insert_with(&Fingerprint2011, &i.to_le_bytes());
+    }
+    let bytes = hll.to_zetasketch(Some(4));
+    assert_eq!(value_type(&bytes), Ok(Some(4)));
+    assert_eq!(HyperLogLog::from_zetasketch(&bytes), Ok(hll.clone()));
+    let error = (hll.cardinality() as f64 - 10000.0).abs() / 10000.0;
+    assert!(error < 0.1, "{error}");
+}
+
+#[test]
+fn zetasketch_fold() {
+    let mut large = HyperLogLog::<[u8; 4096]>::default();
+    let mut small = HyperLogLog::<[u8; 1024]>::default();
+    for i in 0..5000u32 {
+        large.insert_with(&Fingerprint2011, &i.to_le_bytes());
+        small.insert_with(&Fingerprint2011, &i.to_le_bytes());
+    }
+    let bytes = large.to_zetasketch(None);
+    assert_eq!(HyperLogLog::from_zetasketch(&bytes), Ok(small));
+    assert_eq!(
+        HyperLogLog::<[u8; 8192]>::from_zetasketch(&bytes),
+        Err(ZetaSketchError::PrecisionTooLow(12))
+    );
+}
+
+#[test]
+fn zetasketch_invalid() {
+    type Hll = HyperLogLog<[u8; 1024]>;
+    assert_eq!(
+        Hll::from_zetasketch(b""),
+        Err(ZetaSketchError::InvalidProto)
+    );
+    assert_eq!(
+        Hll::from_zetasketch(&SPARSE[..SPARSE.len() - 1]),
+        Err(ZetaSketchError::InvalidProto)
+    );
+
+    // Another aggregator type.
+    let mut bytes = SPARSE.to_vec();
+    bytes[1] = 0x71;
+    assert_eq!(
+        Hll::from_zetasketch(&bytes),
+        Err(ZetaSketchError::InvalidProto)
+    );
+
+    // A sparse index whose extra bits are all zero, 00000 00011 00000.
+    let mut bytes = SPARSE.to_vec();
+    bytes[17] = 0x60;
+    assert_eq!(
+        Hll::from_zetasketch(&bytes),
+        Err(ZetaSketchError::InvalidRegisters)
+    );
+
+    // A dense register past the largest value at precision 10.
+    let mut bytes = Hll::default().to_zetasketch(None);
+    let last = bytes.len() - 1;
+    bytes[last] = 56;
+    assert_eq!(
+        Hll::from_zetasketch(&bytes),
+        Err(ZetaSketchError::InvalidRegisters)
+    );
+    bytes[last] = 55;
+    assert!(Hll::from_zetasketch(&bytes).is_ok());
+}